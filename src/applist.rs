@@ -0,0 +1,224 @@
+use std::{fs, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+
+use crate::backend::BackendResult;
+use crate::request::Requester;
+use crate::session::SessionError;
+use crate::settings::SteamSettings;
+
+// Every app on Steam with its name, for finding the app id of a game by its name.
+// The list is big and barely changes, so it's downloaded once and kept in the cache directory
+
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct App {
+    pub appid: u32,
+    pub name: String
+}
+
+// Same layout as the response of ISteamApps/GetAppList
+#[derive(Serialize, Deserialize)]
+struct AppListJson {
+    applist: Apps
+}
+#[derive(Serialize, Deserialize)]
+struct Apps {
+    apps: Vec<App>
+}
+
+// What a name matched
+pub enum Resolved {
+    App(App),
+    Candidates(Vec<App>), // best match first
+    NotFound
+}
+
+pub struct AppList {
+    apps: Vec<App>
+}
+
+// How many candidates are offered when the name is ambiguous
+const CANDIDATES: usize = 5;
+// Words at least this similar are taken as the same word with a typo
+const SIMILAR_WORD: f64 = 0.75;
+
+impl AppList {
+    pub fn len(&self) -> usize { self.apps.len() }
+
+    pub fn name(&self, app_id: &str) -> Option<String> {
+        let app_id = app_id.parse::<u32>().ok()?;
+        self.apps.iter().find(|app| app.appid == app_id).map(|app| app.name.clone())
+    }
+
+    // Default location of the cached list
+    pub fn cache_path() -> PathBuf {
+        dirs::cache_dir()
+            .unwrap_or_else(|| home::home_dir().unwrap_or_default())
+            .join("rin_scraper")
+            .join("applist.json")
+    }
+
+    // Load the list from the fixture file in the settings, or the cache. It's only downloaded when there's neither
+    pub async fn load(requests: &Requester, settings: &SteamSettings) -> BackendResult<AppList> {
+        if let Some(file) = &settings.app_list_file {
+            return AppList::from_file(Path::new(file));
+        }
+        match AppList::from_file(&AppList::cache_path()) {
+            Ok(list) => Ok(list),
+            Err(_) => AppList::refresh(requests, settings).await
+        }
+    }
+
+    // Download the list again and replace the cache
+    pub async fn refresh(requests: &Requester, settings: &SteamSettings) -> BackendResult<AppList> {
+        let body = requests.get(&settings.app_list_url).await?.error_for_status()?.bytes().await?;
+        let list = AppList::parse(&body)?;
+
+        let path = AppList::cache_path();
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, &body)?;
+
+        Ok(list)
+    }
+
+    fn from_file(path: &Path) -> BackendResult<AppList> {
+        AppList::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(json: &[u8]) -> BackendResult<AppList> {
+        let parsed: AppListJson = serde_json::from_slice(json)
+            .map_err(|e| SessionError::Parse(format!("Steam app list: {}", e)))?;
+        Ok(AppList { apps: parsed.applist.apps })
+    }
+
+    // Find the app id for a name. An exact match (ignoring case and symbols) or a clear winner is taken,
+    // otherwise the closest names are returned
+    pub fn resolve(&self, query: &str) -> Resolved {
+        let query = normalize(query);
+        if query.is_empty() {
+            return Resolved::NotFound;
+        }
+        let words: Vec<&str> = query.split(' ').collect();
+
+        let mut scored: Vec<(f64, &App)> = Vec::new();
+        for app in &self.apps {
+            let name = normalize(&app.name);
+            let name_words: Vec<&str> = name.split(' ').collect();
+            // How close each word of the query is to a word of the name, so typos like "witchr" still count
+            let closest: Vec<f64> = words.iter()
+                .map(|w| name_words.iter().map(|n| word_similarity(w, n)).fold(0.0, f64::max))
+                .collect();
+            // Only names that share a word with the query are worth comparing
+            if !closest.iter().any(|s| *s >= SIMILAR_WORD) {
+                continue;
+            }
+            // Names with every word of the query are close even when they're much longer, e.g. "witcher 3" and "the witcher 3 wild hunt"
+            let mut score = similarity(&query, &name);
+            let weakest = closest.iter().cloned().fold(1.0, f64::min);
+            if weakest >= SIMILAR_WORD {
+                score = score.max(weakest * (0.5 + 0.5 * query.len() as f64 / name.len() as f64));
+            }
+            scored.push((score, app));
+        }
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.appid.cmp(&b.1.appid)));
+
+        let exact: Vec<&App> = scored.iter().filter(|(s, _)| *s >= 1.0).map(|(_, a)| *a).collect();
+        if exact.len() == 1 {
+            return Resolved::App(exact[0].clone());
+        }
+
+        match scored.as_slice() {
+            [] => Resolved::NotFound,
+            [(best, app)] if *best >= 0.8 => Resolved::App((*app).clone()),
+            [(best, app), (second, _), ..] if *best >= 0.9 && best - second >= 0.15 => Resolved::App((*app).clone()),
+            _ => Resolved::Candidates(scored.iter().take(CANDIDATES).map(|(_, a)| (*a).clone()).collect())
+        }
+    }
+}
+
+// Lowercase words without symbols, so "DOOM® Eternal" and "doom eternal" are the same
+pub fn normalize(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// Like similarity, but numbers have to be the same, "witcher 2" isn't a typo of "witcher 3"
+fn word_similarity(a: &str, b: &str) -> f64 {
+    match a.chars().all(|c| c.is_ascii_digit()) || b.chars().all(|c| c.is_ascii_digit()) {
+        true => (a == b) as u8 as f64,
+        false => similarity(a, b)
+    }
+}
+
+// 1.0 for the same names, less the more edits it takes to get from one to the other
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    // Levenshtein distance, keeping one row
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (diag + (ca != cb) as usize).min(row[j] + 1).min(row[j + 1] + 1);
+            diag = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+
+    1.0 - row[b.len()] as f64 / longest as f64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list() -> AppList {
+        AppList::parse(include_bytes!("../tests/fixtures/applist.json")).unwrap()
+    }
+
+    fn resolve(query: &str) -> Result<u32, Vec<u32>> {
+        match list().resolve(query) {
+            Resolved::App(app) => Ok(app.appid),
+            Resolved::Candidates(apps) => Err(apps.iter().map(|a| a.appid).collect()),
+            Resolved::NotFound => Err(Vec::new())
+        }
+    }
+
+    #[test]
+    fn resolves_names() {
+        assert_eq!(resolve("The Witcher 3: Wild Hunt"), Ok(292030));
+        assert_eq!(resolve("the witcher 3 wild hunt"), Ok(292030));
+        assert_eq!(resolve("DOOM® Eternal"), Ok(782330));
+        assert_eq!(resolve("doom"), Ok(379720));
+        assert_eq!(resolve("Red Dead Redemtion 2"), Ok(1174180));
+    }
+
+    #[test]
+    fn offers_candidates() {
+        assert_eq!(resolve("witcher 3").map_err(|c| c[..3].to_vec()), Err(vec![292030, 378648, 378649]));
+        // Typos still find the game
+        assert_eq!(resolve("witchr 3").map_err(|c| c[0]), Err(292030));
+        assert_eq!(resolve("portl").map_err(|c| c[..2].to_vec()), Err(vec![400, 620]));
+        assert_eq!(resolve("cyberpnk").map_err(|c| c[0]), Err(1091500));
+    }
+
+    #[test]
+    fn finds_nothing() {
+        assert_eq!(resolve("xyzzy"), Err(Vec::new()));
+        assert_eq!(resolve("®"), Err(Vec::new()));
+        assert_eq!(word_similarity("2", "3"), 0.0);
+        assert_eq!(list().name("620").as_deref(), Some("Portal 2"));
+    }
+}
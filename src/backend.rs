@@ -0,0 +1,414 @@
+use std::{collections::HashMap, fmt, process::Child, time::Duration};
+use async_trait::async_trait;
+use reqwest::Url;
+use scraper::{Html, Selector, ElementRef, Node};
+use thirtyfour::{prelude::*, error::WebDriverError};
+
+use crate::scrape::browser_init;
+use crate::session::{HttpSession, SessionError, FORUM_URL};
+use crate::request::Requester;
+
+
+// Errors from any of the backends
+#[derive(Debug)]
+pub enum BackendError {
+    WebDriver(WebDriverError),
+    Session(SessionError),
+    Io(std::io::Error),
+    NotFound(String),
+    LoginFailed(String),
+    LoggedOut
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::WebDriver(e) => write!(f, "WebDriver error: {}", e),
+            BackendError::Session(e) => write!(f, "{}", e),
+            BackendError::Io(e) => write!(f, "{}", e),
+            BackendError::NotFound(selector) => write!(f, "Couldn't find element: {}", selector),
+            BackendError::LoginFailed(msg) => write!(f, "Login failed: {}", msg),
+            BackendError::LoggedOut => write!(f, "Not logged in to the forum")
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+impl From<WebDriverError> for BackendError {
+    fn from(e: WebDriverError) -> BackendError { BackendError::WebDriver(e) }
+}
+impl From<SessionError> for BackendError {
+    fn from(e: SessionError) -> BackendError { BackendError::Session(e) }
+}
+impl From<reqwest::Error> for BackendError {
+    fn from(e: reqwest::Error) -> BackendError { BackendError::Session(SessionError::Http(e)) }
+}
+impl From<std::io::Error> for BackendError {
+    fn from(e: std::io::Error) -> BackendError { BackendError::Io(e) }
+}
+
+pub type BackendResult<T> = Result<T, BackendError>;
+
+// Name and value pairs sent with a form
+type FormValues = Vec<(String, String)>;
+
+
+// An element found on a page, detached from the page so it works the same for every backend
+#[derive(Debug, Clone)]
+pub struct Element {
+    text: String,
+    attrs: HashMap<String, String>
+}
+
+impl Element {
+    fn from_ref(elem: &ElementRef) -> Element {
+        Element {
+            text: elem.text().collect::<String>().trim().to_string(),
+            attrs: elem.value().attrs().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        }
+    }
+
+    pub fn text(&self) -> String { self.text.clone() }
+    pub fn attr(&self, name: &str) -> Option<String> { self.attrs.get(name).cloned() }
+}
+
+
+// The way we talk to the forum, so the scraping logic doesn't care if it's a browser or plain HTTP
+#[async_trait]
+pub trait ForumBackend: Send + Sync {
+    // Address of the forum
+    fn base_url(&self) -> String;
+    // Full url of a forum page
+    fn url(&self, path: &str) -> String;
+    // If javascript runs on the pages, plain HTTP has to do some things (like PrivateBin) by itself
+    fn runs_scripts(&self) -> bool;
+    // Request layer for everything that isn't a forum page
+    fn requests(&self) -> Requester;
+
+    async fn navigate(&mut self, url: &str) -> BackendResult<()>;
+    async fn current_url(&self) -> BackendResult<String>;
+    async fn page_source(&self) -> BackendResult<String>;
+
+    // Fill in the fields (css selector, value) of the form containing the first field and submit it with the submit button
+    async fn submit_form(&mut self, fields: &[(&str, &str)], submit: &str) -> BackendResult<()>;
+
+    // Cookies of the forum as name and value, used for saving the session
+    async fn cookies(&self) -> BackendResult<Vec<(String, String)>>;
+    async fn add_cookies(&mut self, cookies: &[(String, String)]) -> BackendResult<()>;
+
+    // Close everything that was started for the backend
+    async fn quit(self: Box<Self>) -> BackendResult<()>;
+
+    // Elements on the current page matching the css selector
+    async fn find_all(&self, selector: &str) -> BackendResult<Vec<Element>> {
+        select_all(&Html::parse_document(&self.page_source().await?), selector)
+    }
+    async fn find(&self, selector: &str) -> BackendResult<Element> {
+        first(self.find_all(selector).await?, selector)
+    }
+}
+
+// Pick the backend with RIN_BACKEND ("browser" or "http"), the browser is the default.
+// RIN_URL can be used to change the address of the forum (e.g. for a local mock server)
+pub async fn from_env(requests: Requester) -> BackendResult<Box<dyn ForumBackend>> {
+    let base_url = std::env::var("RIN_URL").unwrap_or_else(|_| FORUM_URL.to_string());
+
+    // Replayed pages are already there, they don't need a browser
+    let name = match requests.is_replay() {
+        true => "http".to_string(),
+        false => std::env::var("RIN_BACKEND").unwrap_or_default().to_lowercase()
+    };
+
+    match name.as_str() {
+        "http" => Ok(Box::new(HttpBackend::new(HttpSession::new(&base_url, requests)?))),
+        _ => {
+            let (driver, geckodriver) = browser_init(&requests.proxy()).await?;
+            Ok(Box::new(WebDriverBackend::new(driver, geckodriver, &base_url, requests)))
+        }
+    }
+}
+
+// Wait until an element shows up on the page, used when scripts have to run first
+pub async fn wait_for(backend: &dyn ForumBackend, selector: &str, timeout: Duration) -> BackendResult<Element> {
+    let step = Duration::from_secs_f32(0.2);
+    let mut waited = Duration::ZERO;
+    loop {
+        match backend.find(selector).await {
+            Ok(elem) => return Ok(elem),
+            Err(BackendError::NotFound(_)) if backend.runs_scripts() && waited < timeout => {
+                tokio::time::sleep(step).await;
+                waited += step;
+            }
+            Err(e) => return Err(e)
+        }
+    }
+}
+
+// Resolve a link from a page relative to its url
+pub fn join_url(base: &str, href: &str) -> BackendResult<String> {
+    Url::parse(base)
+        .and_then(|url| url.join(&href.replace("&amp;", "&")))
+        .map(|url| url.to_string())
+        .map_err(|e| SessionError::Parse(e.to_string()).into())
+}
+
+fn select_all(doc: &Html, selector: &str) -> BackendResult<Vec<Element>> {
+    let sel = Selector::parse(selector)
+        .map_err(|_| SessionError::Parse(format!("Invalid selector: {}", selector)))?;
+    Ok(doc.select(&sel).map(|e| Element::from_ref(&e)).collect())
+}
+
+fn first(elems: Vec<Element>, selector: &str) -> BackendResult<Element> {
+    elems.into_iter().next().ok_or_else(|| BackendError::NotFound(selector.to_string()))
+}
+
+
+// Firefox driven through geckodriver
+pub struct WebDriverBackend {
+    driver: WebDriver,
+    geckodriver: Child,
+    base_url: String,
+    requests: Requester
+}
+
+impl WebDriverBackend {
+    pub fn new(driver: WebDriver, geckodriver: Child, base_url: &str, requests: Requester) -> WebDriverBackend {
+        WebDriverBackend { driver, geckodriver, base_url: base_url.trim_end_matches('/').to_string(), requests }
+    }
+}
+
+#[async_trait]
+impl ForumBackend for WebDriverBackend {
+    fn base_url(&self) -> String { self.base_url.clone() }
+    fn url(&self, path: &str) -> String { format!("{}/{}", self.base_url, path.trim_start_matches('/')) }
+    fn runs_scripts(&self) -> bool { true }
+    fn requests(&self) -> Requester { self.requests.clone() }
+
+    async fn navigate(&mut self, url: &str) -> BackendResult<()> {
+        self.requests.goto(&self.driver, url).await?;
+        // Saved under the url that was asked for, so replaying the same navigation finds it
+        self.requests.record_page(url, &self.current_url().await?, &self.driver.source().await?);
+        Ok(())
+    }
+    async fn current_url(&self) -> BackendResult<String> {
+        Ok(self.driver.current_url().await?.to_string())
+    }
+    async fn page_source(&self) -> BackendResult<String> {
+        let source = self.driver.source().await?;
+        let url = self.current_url().await?;
+        self.requests.record_page(&url, &url, &source);
+        Ok(source)
+    }
+
+    async fn submit_form(&mut self, fields: &[(&str, &str)], submit: &str) -> BackendResult<()> {
+        let (first_field, _) = fields.first().ok_or_else(|| BackendError::NotFound("form fields".to_string()))?;
+        let form = self.driver.find(By::Css(first_field)).await?.find(By::XPath("./ancestor::form")).await?;
+
+        for (selector, value) in fields {
+            form.find(By::Css(selector)).await?.send_keys(*value).await?;
+        }
+        form.find(By::Css(submit)).await?.click().await?;
+        Ok(())
+    }
+
+    async fn cookies(&self) -> BackendResult<Vec<(String, String)>> {
+        Ok(self.driver.get_all_cookies().await?.iter()
+            .map(|c| (c.name().to_string(), c.value().to_string()))
+            .collect())
+    }
+    async fn add_cookies(&mut self, cookies: &[(String, String)]) -> BackendResult<()> {
+        // Cookies can only be added for the site that is open
+        self.requests.goto(&self.driver, &self.base_url).await?;
+        for (name, value) in cookies {
+            let mut cookie = Cookie::new(name.clone(), value.clone());
+            cookie.set_path("/");
+            self.driver.add_cookie(cookie).await?;
+        }
+        Ok(())
+    }
+
+    async fn quit(self: Box<Self>) -> BackendResult<()> {
+        let mut this = *self;
+        // Quit the driver and kill the geckodriver thread
+        this.driver.quit().await?;
+        this.geckodriver.kill()?;
+        Ok(())
+    }
+}
+
+
+// Plain HTTP requests, pages are parsed as they come from the server without running any scripts
+pub struct HttpBackend {
+    session: HttpSession,
+    url: String,
+    page: String
+}
+
+impl HttpBackend {
+    pub fn new(session: HttpSession) -> HttpBackend {
+        HttpBackend { session, url: String::new(), page: String::new() }
+    }
+}
+
+#[async_trait]
+impl ForumBackend for HttpBackend {
+    fn base_url(&self) -> String { self.session.base_url() }
+    fn url(&self, path: &str) -> String { self.session.url(path) }
+    fn runs_scripts(&self) -> bool { false }
+    fn requests(&self) -> Requester { self.session.requests() }
+
+    async fn navigate(&mut self, url: &str) -> BackendResult<()> {
+        (self.url, self.page) = self.session.get(url).await?;
+        Ok(())
+    }
+    async fn current_url(&self) -> BackendResult<String> {
+        Ok(self.url.clone())
+    }
+    async fn page_source(&self) -> BackendResult<String> {
+        Ok(self.page.clone())
+    }
+
+    async fn submit_form(&mut self, fields: &[(&str, &str)], submit: &str) -> BackendResult<()> {
+        let (method, action, values) = {
+            let doc = Html::parse_document(&self.page);
+            form_values(&doc, fields, submit)?
+        };
+        let action = join_url(&self.url, &action)?;
+
+        (self.url, self.page) = match method.as_str() {
+            "post" => self.session.post_form(&action, &values).await?,
+            _ => {
+                let url = Url::parse_with_params(&action, &values).map_err(|e| SessionError::Parse(e.to_string()))?;
+                self.session.get(url.as_str()).await?
+            }
+        };
+        Ok(())
+    }
+
+    async fn cookies(&self) -> BackendResult<Vec<(String, String)>> {
+        Ok(self.session.cookies()?)
+    }
+    async fn add_cookies(&mut self, cookies: &[(String, String)]) -> BackendResult<()> {
+        Ok(self.session.add_cookies(cookies)?)
+    }
+
+    async fn quit(self: Box<Self>) -> BackendResult<()> {
+        Ok(())
+    }
+}
+
+// Get the method, action and the values (prefilled ones like sid, form_token, creation_time included) of a form
+fn form_values(doc: &Html, fields: &[(&str, &str)], submit: &str) -> BackendResult<(String, String, FormValues)> {
+    let parse = |selector: &str| Selector::parse(selector)
+        .map_err(|_| BackendError::Session(SessionError::Parse(format!("Invalid selector: {}", selector))));
+
+    let (first_field, _) = fields.first().ok_or_else(|| BackendError::NotFound("form fields".to_string()))?;
+    let form = doc.select(&parse(first_field)?).next()
+        .and_then(|field| field.ancestors().find(|n| matches!(n.value(), Node::Element(e) if e.name() == "form")))
+        .and_then(ElementRef::wrap)
+        .ok_or_else(|| BackendError::NotFound(format!("form with {}", first_field)))?;
+
+    let method = form.value().attr("method").unwrap_or("get").to_lowercase();
+    let action = form.value().attr("action").unwrap_or("").to_string();
+
+    // Values that are already in the form
+    let mut values: FormValues = Vec::new();
+    for input in form.select(&parse("input")?) {
+        let kind = input.value().attr("type").unwrap_or("text");
+        if kind == "submit" || kind == "button" || ((kind == "checkbox" || kind == "radio") && input.value().attr("checked").is_none()) {
+            continue;
+        }
+        if let Some(name) = input.value().attr("name") {
+            values.push((name.to_string(), input.value().attr("value").unwrap_or("").to_string()));
+        }
+    }
+
+    // Fill in the fields
+    for (selector, value) in fields {
+        let name = form.select(&parse(selector)?).next()
+            .and_then(|e| e.value().attr("name"))
+            .ok_or_else(|| BackendError::NotFound(selector.to_string()))?;
+        values.retain(|(n, _)| n != name);
+        values.push((name.to_string(), value.to_string()));
+    }
+
+    // And the button that was "clicked"
+    let button = form.select(&parse(submit)?).next().ok_or_else(|| BackendError::NotFound(submit.to_string()))?;
+    if let Some(name) = button.value().attr("name") {
+        values.push((name.to_string(), button.value().attr("value").unwrap_or("").to_string()));
+    }
+
+    Ok((method, action, values))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockServer, MockResponse};
+
+    const SID: &str = "fedcba9876543210fedcba9876543210";
+
+    async fn forum() -> (MockServer, HttpBackend) {
+        let server = MockServer::start().await;
+        server.route("GET", "/index.php", vec![MockResponse::page(include_str!("../tests/fixtures/index_logged_in.html"))]);
+        server.route("GET", "/search.php", vec![
+            MockResponse::page(include_str!("../tests/fixtures/search_form.html")),
+            MockResponse::page(include_str!("../tests/fixtures/index_logged_in.html"))
+        ]);
+        let backend = HttpBackend::new(HttpSession::new(&server.url(), server.requester()).unwrap());
+        (server, backend)
+    }
+
+    #[tokio::test]
+    async fn http_backend_reads_pages() {
+        let (server, mut backend) = forum().await;
+        let index = backend.url("index.php");
+        assert_eq!(index, format!("{}/index.php", server.url()));
+
+        backend.navigate(&index).await.unwrap();
+        assert_eq!(backend.current_url().await.unwrap(), index);
+        let forums = backend.find_all("a.forumlink").await.unwrap();
+        assert_eq!(forums.len(), 1);
+        assert_eq!(forums[0].text(), "Steam Content Sharing");
+        assert_eq!(join_url(&index, &forums[0].attr("href").unwrap()).unwrap(), format!("{}/viewforum.php?f=22&sid={}", server.url(), SID));
+        assert!(matches!(backend.find("a.topictitle").await, Err(BackendError::NotFound(_))));
+
+        // The sid from the page goes into the next urls
+        assert_eq!(backend.url("search.php"), format!("{}/search.php?sid={}", server.url(), SID));
+        assert_eq!(backend.url("search.php?sid=0"), format!("{}/search.php?sid=0", server.url()));
+    }
+
+    #[tokio::test]
+    async fn http_backend_follows_redirects() {
+        let (server, mut backend) = forum().await;
+        let location = format!("{}/index.php", server.url());
+        server.route("GET", "/ucp.php", vec![MockResponse { status: 302, headers: vec![("Location".to_string(), location.clone())], body: String::new() }]);
+
+        backend.navigate(&backend.url("ucp.php?mode=login")).await.unwrap();
+        assert_eq!(backend.current_url().await.unwrap(), location);
+        assert_eq!(backend.find("a.forumlink").await.unwrap().text(), "Steam Content Sharing");
+    }
+
+    #[tokio::test]
+    async fn http_backend_submits_get_forms() {
+        let (server, mut backend) = forum().await;
+        backend.add_cookies(&[("phpbb3_rin_u".to_string(), "2".to_string())]).await.unwrap();
+        backend.navigate(&backend.url("search.php")).await.unwrap();
+        backend.submit_form(&[("input[name=keywords]", "witcher 3"), ("input[name=author]", "")], "input[name=submit]").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|r| r.headers["cookie"].contains("phpbb3_rin_u=2")));
+
+        // Only the checked radio and checkbox are sent
+        let search = Url::parse(&format!("{}{}", server.url(), requests[1].path)).unwrap();
+        let query: Vec<(String, String)> = search.query_pairs().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let expected = [("terms", "all"), ("sc", "1"), ("sid", SID), ("keywords", "witcher 3"), ("author", ""), ("submit", "Search")];
+        assert_eq!(query, expected.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<_>>());
+
+        assert_eq!(backend.current_url().await.unwrap(), search.to_string());
+        assert_eq!(backend.find("a.forumlink").await.unwrap().text(), "Steam Content Sharing");
+    }
+}
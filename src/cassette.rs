@@ -0,0 +1,170 @@
+use std::{collections::HashMap, fs, io::Error, path::{Path, PathBuf}, sync::Mutex};
+use serde::{Serialize, Deserialize};
+use reqwest::Url;
+
+// Responses saved with --record, and served back with --replay without touching the network.
+// The directory has an index.json with the entries and a file with the body of each one
+
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub key: String, // method and url, see key()
+    pub url: String, // where the response came from after redirects
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub file: String
+}
+
+#[derive(PartialEq)]
+enum Mode { Record, Replay }
+
+pub struct Cassette {
+    dir: PathBuf,
+    mode: Mode,
+    entries: Mutex<Vec<Entry>>,
+    served: Mutex<HashMap<String, usize>> // key, how many times it was replayed
+}
+
+impl Cassette {
+    // Start recording into the directory, entries that are already there are kept
+    pub fn record(dir: &Path) -> Result<Cassette, Error> {
+        fs::create_dir_all(dir)?;
+        let entries = Cassette::read_index(dir).unwrap_or_default();
+        Ok(Cassette { dir: dir.to_path_buf(), mode: Mode::Record, entries: Mutex::new(entries), served: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn replay(dir: &Path) -> Result<Cassette, Error> {
+        let entries = Cassette::read_index(dir)?;
+        Ok(Cassette { dir: dir.to_path_buf(), mode: Mode::Replay, entries: Mutex::new(entries), served: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn is_replay(&self) -> bool { self.mode == Mode::Replay }
+    pub fn is_recording(&self) -> bool { self.mode == Mode::Record }
+
+    fn read_index(dir: &Path) -> Result<Vec<Entry>, Error> {
+        let f = fs::File::open(dir.join("index.json"))?;
+        Ok(serde_json::from_reader(f)?)
+    }
+
+    // The index is written after every entry, so a run that crashes still leaves a usable cassette
+    fn write_index(&self, entries: &[Entry]) -> Result<(), Error> {
+        let f = fs::File::create(self.dir.join("index.json"))?;
+        serde_json::to_writer_pretty(f, entries)?;
+        Ok(())
+    }
+
+    // Save a response
+    pub fn save(&self, key: &str, url: &str, status: u16, content_type: Option<String>, body: &[u8]) -> Result<(), Error> {
+        if self.mode != Mode::Record {
+            return Ok(());
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let file = format!("{:04}.body", entries.len());
+        fs::write(self.dir.join(&file), body)?;
+
+        entries.push(Entry { key: key.to_string(), url: url.to_string(), status, content_type, file });
+        self.write_index(&entries)
+    }
+
+    // Save the source of a page open in the browser. Scripts can change the page after it loads,
+    // so the last source of the same page replaces the one before it. final_url is where the browser
+    // ended up after redirects
+    pub fn save_page(&self, url: &str, final_url: &str, source: &str) -> Result<(), Error> {
+        if self.mode != Mode::Record {
+            return Ok(());
+        }
+        let key = key("GET", url);
+        let last = {
+            let entries = self.entries.lock().unwrap();
+            entries.last().filter(|e| e.key == key).cloned()
+        };
+
+        match last {
+            Some(entry) => fs::write(self.dir.join(entry.file), source),
+            None => self.save(&key, final_url, 200, Some("text/html; charset=utf-8".to_string()), source.as_bytes())
+        }
+    }
+
+    // The next recorded response for the key. Responses are served in the order they were recorded,
+    // the last one is repeated once they run out
+    pub fn load(&self, key: &str) -> Option<(Entry, Vec<u8>)> {
+        let entries = self.entries.lock().unwrap();
+        let matching: Vec<&Entry> = entries.iter().filter(|e| e.key == key).collect();
+
+        let mut served = self.served.lock().unwrap();
+        let count = served.entry(key.to_string()).or_insert(0);
+        let entry = matching.get(*count).or_else(|| matching.last())?;
+        *count += 1;
+
+        let body = fs::read(self.dir.join(&entry.file)).ok()?;
+        Some(((*entry).clone(), body))
+    }
+}
+
+// Key of a request in the cassette. The session id changes with every login, so it's left out
+pub fn key(method: &str, url: &str) -> String {
+    let url = match Url::parse(url) {
+        Ok(mut url) => {
+            let pairs: Vec<(String, String)> = url.query_pairs()
+                .filter(|(k, _)| k != "sid")
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            if pairs.is_empty() {
+                url.set_query(None);
+            } else {
+                url.query_pairs_mut().clear().extend_pairs(pairs);
+            }
+            url.to_string()
+        }
+        Err(_) => url.to_string()
+    };
+    format!("{} {}", method, url)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rin_scraper_cassette_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn keys_leave_out_the_sid() {
+        let base = "https://cs.rin.ru/forum";
+        assert_eq!(key("GET", &format!("{}/viewtopic.php?t=1&sid=0123&start=15", base)), format!("GET {}/viewtopic.php?t=1&start=15", base));
+        assert_eq!(key("POST", &format!("{}/ucp.php?sid=0123", base)), format!("POST {}/ucp.php", base));
+        assert_eq!(key("GET", &format!("{}/search.php?keywords=witcher+3", base)), format!("GET {}/search.php?keywords=witcher+3", base));
+        // The order of the parameters is kept
+        assert_ne!(key("GET", "https://a.example/?a=1&b=2"), key("GET", "https://a.example/?b=2&a=1"));
+        assert_eq!(key("GET", "not a url"), "GET not a url");
+    }
+
+    #[test]
+    fn replays_in_the_recorded_order() {
+        let dir = temp_dir("order");
+        let recording = Cassette::record(&dir).unwrap();
+        let first = key("GET", "https://a.example/page");
+        recording.save(&first, "https://a.example/page", 200, None, b"one").unwrap();
+        recording.save(&key("GET", "https://a.example/other"), "https://a.example/other", 200, None, b"other").unwrap();
+        recording.save(&first, "https://a.example/moved", 200, None, b"two").unwrap();
+        // A browser page that changed keeps only its last source
+        recording.save_page("https://a.example/browser", "https://a.example/browser", "loading").unwrap();
+        recording.save_page("https://a.example/browser", "https://a.example/browser", "done").unwrap();
+
+        let replay = Cassette::replay(&dir).unwrap();
+        let body = |key: &str| String::from_utf8(replay.load(key).unwrap().1).unwrap();
+        assert_eq!(body(&first), "one");
+        let (entry, _) = replay.load(&first).unwrap();
+        assert_eq!((entry.url.as_str(), entry.file.as_str()), ("https://a.example/moved", "0002.body"));
+        // The last one repeats
+        assert_eq!(body(&first), "two");
+        assert_eq!(body(&key("GET", "https://a.example/browser")), "done");
+        assert!(replay.load(&key("GET", "https://a.example/missing")).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
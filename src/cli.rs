@@ -0,0 +1,202 @@
+use std::path::PathBuf;
+
+use crate::query::{SearchQuery, Terms, SearchIn, SortKey, SortDir};
+
+pub const USAGE: &str = "Usage:
+rin-scraper [options] {query}
+rin-scraper createconfig
+rin-scraper refreshapps
+rin-scraper cache clear
+
+Query is the keyword that you want to search with, SteamAppID is recommended.
+Names are looked up in the Steam app list and replaced with their AppID, refreshapps downloads the list again.
+Info about Steam apps is cached, cache clear removes it
+
+Search options:
+  --no-resolve                        Search for the query as it is, without looking up its AppID
+  --no-cache                          Get the info about Steam apps again, even if it's cached
+  --terms all|any                     Match all or any of the keywords (default: any)
+  --in titles|firstpost|fulltext      Where to search for the keywords (default: firstpost)
+  --sort time|author|title|forum|subject
+                                      What to sort the results by (default: time)
+  --order asc|desc                    Sort direction (default: desc)
+  --author NAME                       Only topics by this author
+  --days N                            Only posts from the last N days (default: 0, all)
+  --forum ID                          Search in this forum, can be used more than once (default: 22, SCS)
+
+Output:
+  --report FILE                       Also save how fresh the releases are compared to Steam as JSON to FILE
+
+Debugging:
+  --record DIR                        Save every response and page to DIR
+  --replay DIR                        Serve the responses saved with --record from DIR, without the network";
+
+
+// Subcommands, they're run instead of a search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    CreateConfig,
+    RefreshApps,
+    ClearCache
+}
+
+// Arguments from the command line
+#[derive(Debug, Default)]
+pub struct Cli {
+    query: Option<String>,
+    command: Option<Command>,
+    terms: Option<Terms>,
+    search_in: Option<SearchIn>,
+    sort_key: Option<SortKey>,
+    sort_dir: Option<SortDir>,
+    author: Option<String>,
+    days: Option<u32>,
+    forums: Vec<u32>,
+    no_resolve: bool,
+    no_cache: bool,
+    report: Option<PathBuf>,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>
+}
+
+impl Cli {
+    // Parse the arguments, without the program name
+    pub fn parse(args: &[String]) -> Result<Cli, String> {
+        let mut cli = Cli::default();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            // Words that aren't options are all part of the query, so names don't need quotes
+            if !arg.starts_with("--") {
+                cli.query = match &cli.query {
+                    Some(query) => Some(format!("{query} {arg}")),
+                    None => Some(arg.clone())
+                };
+                continue;
+            }
+
+            match arg.as_str() {
+                "--no-resolve" => { cli.no_resolve = true; continue }
+                "--no-cache" => { cli.no_cache = true; continue }
+                _ => {}
+            }
+
+            let mut value = || iter.next().cloned().ok_or_else(|| format!("Missing value for {arg}"));
+            match arg.as_str() {
+                "--terms" => cli.terms = Some(value()?.parse()?),
+                "--in" => cli.search_in = Some(value()?.parse()?),
+                "--sort" => cli.sort_key = Some(value()?.parse()?),
+                "--order" => cli.sort_dir = Some(value()?.parse()?),
+                "--author" => cli.author = Some(value()?),
+                "--days" => cli.days = Some(parse_number(&value()?)?),
+                "--forum" => cli.forums.push(parse_number(&value()?)?),
+                "--report" => cli.report = Some(PathBuf::from(value()?)),
+                "--record" => cli.record = Some(PathBuf::from(value()?)),
+                "--replay" => cli.replay = Some(PathBuf::from(value()?)),
+                _ => return Err(format!("Unknown option: '{arg}'"))
+            }
+        }
+
+        if cli.record.is_some() && cli.replay.is_some() {
+            return Err("--record and --replay can't be used together".to_string());
+        }
+
+        // A query that is just a subcommand isn't searched for
+        cli.command = match cli.query.as_deref().map(|q| q.to_lowercase()).as_deref() {
+            Some("createconfig") => Some(Command::CreateConfig),
+            Some("refreshapps") => Some(Command::RefreshApps),
+            Some("cache clear") => Some(Command::ClearCache),
+            _ => None
+        };
+        if cli.command.is_some() {
+            cli.query = None;
+        }
+        Ok(cli)
+    }
+
+    pub fn query(&self) -> Option<String> { self.query.clone() }
+    pub fn command(&self) -> Option<Command> { self.command }
+    // If names in the query should be turned into app ids
+    pub fn resolve(&self) -> bool { !self.no_resolve }
+    pub fn no_cache(&self) -> bool { self.no_cache }
+    pub fn report(&self) -> Option<PathBuf> { self.report.clone() }
+    pub fn record(&self) -> Option<PathBuf> { self.record.clone() }
+    pub fn replay(&self) -> Option<PathBuf> { self.replay.clone() }
+
+    // Search for the keywords with the options from the command line
+    pub fn search_query(&self, keywords: &str) -> SearchQuery {
+        let mut query = SearchQuery::new(keywords);
+
+        if let Some(terms) = self.terms { query = query.terms(terms) }
+        if let Some(search_in) = self.search_in { query = query.search_in(search_in) }
+        if let Some(sort_key) = self.sort_key { query = query.sort_key(sort_key) }
+        if let Some(sort_dir) = self.sort_dir { query = query.sort_dir(sort_dir) }
+        if let Some(author) = &self.author { query = query.author(author) }
+        if let Some(days) = self.days { query = query.days(days) }
+        if !self.forums.is_empty() { query = query.forums(self.forums.clone()) }
+
+        query
+    }
+}
+
+fn parse_number(value: &str) -> Result<u32, String> {
+    value.parse::<u32>().map_err(|_| format!("Invalid number: '{value}'"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::parse(&args.iter().map(|a| a.to_string()).collect::<Vec<String>>()).unwrap()
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse(&["refreshapps"]).command(), Some(Command::RefreshApps));
+        assert_eq!(parse(&["CreateConfig"]).command(), Some(Command::CreateConfig));
+        let cli = parse(&["cache", "clear", "--no-cache"]);
+        assert_eq!((cli.command(), cli.query()), (Some(Command::ClearCache), None));
+
+        // Anything else is searched for
+        let cli = parse(&["the", "cache", "clear"]);
+        assert_eq!((cli.command(), cli.query().as_deref()), (None, Some("the cache clear")));
+        assert_eq!(parse(&["--no-resolve"]).command(), None);
+    }
+
+    fn error(args: &[&str]) -> String {
+        Cli::parse(&args.iter().map(|a| a.to_string()).collect::<Vec<String>>()).unwrap_err()
+    }
+
+    #[test]
+    fn parses_search_options() {
+        let cli = parse(&["--terms", "all", "witcher", "--in", "titles", "3", "--sort", "title", "--order", "asc",
+            "--author", "Some One", "--days", "30", "--forum", "10", "--forum", "22", "--no-resolve"]);
+        assert_eq!(cli.query().as_deref(), Some("witcher 3"));
+        assert!(!cli.resolve());
+        assert_eq!(cli.search_query("292030").path(0),
+            "search.php?keywords=292030&terms=all&author=Some+One&fid%5B%5D=10&fid%5B%5D=22&sc=1&sf=titleonly&sk=i&sd=a&sr=topics&st=30&ch=300&t=0&start=0&submit=Search");
+
+        // Without options it's the default search
+        let cli = parse(&["292030"]);
+        assert!(cli.resolve());
+        assert_eq!(cli.search_query("292030").path(0), SearchQuery::new("292030").path(0));
+
+        let cli = parse(&["--report", "report.json", "--record", "rec", "x"]);
+        assert_eq!((cli.report(), cli.record(), cli.replay()), (Some(PathBuf::from("report.json")), Some(PathBuf::from("rec")), None));
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert_eq!(error(&["--terms", "some", "x"]), "Invalid terms: 'some', expected all or any");
+        assert!(error(&["--in", "posts"]).starts_with("Invalid search field"));
+        assert!(error(&["--sort", "date"]).starts_with("Invalid sort key"));
+        assert!(error(&["--order", "up"]).starts_with("Invalid sort direction"));
+        assert_eq!(error(&["--days", "-1"]), "Invalid number: '-1'");
+        assert_eq!(error(&["--forum", "scs"]), "Invalid number: 'scs'");
+        assert_eq!(error(&["x", "--author"]), "Missing value for --author");
+        assert_eq!(error(&["--verbose", "x"]), "Unknown option: '--verbose'");
+        assert_eq!(error(&["--record", "a", "--replay", "b"]), "--record and --replay can't be used together");
+    }
+}
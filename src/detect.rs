@@ -0,0 +1,281 @@
+use std::{fs, io::Error, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+
+use crate::vdf;
+
+// What's installed in a game folder: the app id, build and when it was installed.
+// Our own install manifest and Steam's appmanifest are the most exact, releases usually only have
+// steam_appid.txt and the ini of an emulator
+
+
+#[derive(Debug, Default)]
+pub struct Installed {
+    pub app_id: Option<String>,
+    pub build_id: Option<String>,
+    pub installed: Option<i64>, // epoch
+    pub title: Option<String>, // of the release on SCS, only in our install manifest
+    pub variant: Option<Vec<String>>, // same
+    pub sources: Vec<PathBuf> // files the info came from
+}
+
+impl Installed {
+    // Keep what's already known, the sources are checked from the most exact one
+    fn merge(&mut self, app_id: Option<String>, build_id: Option<String>, installed: Option<i64>, source: &Path) {
+        if app_id.is_none() && build_id.is_none() {
+            return;
+        }
+        if self.app_id.is_none() { self.app_id = app_id }
+        if self.build_id.is_none() { self.build_id = build_id }
+        if self.installed.is_none() { self.installed = installed }
+        self.sources.push(source.to_path_buf());
+    }
+}
+
+// Written to the game folder when an update is downloaded, so the next config starts from it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstallManifest {
+    pub app_id: String,
+    pub title: String,
+    pub build_id: Option<String>,
+    #[serde(default)]
+    pub variant: Vec<String>, // platform and tags of the release
+    pub released: i64, // epoch, date of the release
+    pub installed: i64, // epoch
+    pub link: String // mirror it was downloaded from
+}
+
+pub const INSTALL_MANIFEST: &str = "rin_scraper_install.json";
+
+impl InstallManifest {
+    pub fn from_dir(game_dir: &Path) -> Option<InstallManifest> {
+        let f = fs::File::open(game_dir.join(INSTALL_MANIFEST)).ok()?;
+        serde_json::from_reader(f).ok()
+    }
+    pub fn to_dir(&self, game_dir: &Path) -> Result<(), Error> {
+        let f = fs::File::create(game_dir.join(INSTALL_MANIFEST))?;
+        serde_json::to_writer_pretty(f, &self)?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct AppManifest {
+    #[serde(alias = "appID")]
+    appid: String,
+    buildid: Option<String>,
+    installdir: Option<String>,
+    #[serde(rename = "LastUpdated")]
+    last_updated: Option<i64>
+}
+
+// Ini files of Steam emulators that have the app id or the build
+const EMULATOR_INIS: [&str; 10] = [
+    "steam_emu.ini", "steam_api.ini", "smartsteamemu.ini", "cream_api.ini", "ali213.ini",
+    "steamconfig.ini", "valve.ini", "cpy.ini", "configs.app.ini", "configs.main.ini"
+];
+// How deep to look for them, the exe is often in something like Binaries/Win64
+const MAX_DEPTH: usize = 3;
+
+fn is_app_id(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+// When the file was last written, which is about when the game was installed
+fn modified(path: &Path) -> Option<i64> {
+    let time = fs::metadata(path).ok()?.modified().ok()?;
+    Some(chrono::DateTime::<chrono::Utc>::from(time).timestamp())
+}
+
+// Files in the folder and its subfolders, up to MAX_DEPTH
+fn files(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.is_dir() {
+            if depth < MAX_DEPTH {
+                files(&path, depth + 1, found);
+            }
+        } else {
+            found.push(path);
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+fn read_manifest(path: &Path) -> Option<AppManifest> {
+    let text = fs::read_to_string(path).ok()?;
+    vdf::from_str::<AppManifest>(&text).ok().filter(|m| is_app_id(&m.appid))
+}
+
+// The appmanifest of a game in a Steam library: steamapps/common/{installdir} has steamapps/appmanifest_{appid}.acf
+fn library_manifest(game_dir: &Path) -> Option<(PathBuf, AppManifest)> {
+    let dir_name = game_dir.file_name()?.to_string_lossy().to_string();
+    let steamapps = game_dir.parent().filter(|p| file_name(p) == "common")?.parent()?;
+    fs::read_dir(steamapps).ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| file_name(p).starts_with("appmanifest_") && file_name(p).ends_with(".acf"))
+        .filter_map(|p| read_manifest(&p).map(|m| (p, m)))
+        .find(|(_, m)| m.installdir.as_deref() == Some(dir_name.as_str()))
+}
+
+// App id and build from "key = value" lines, sections and comments are skipped
+fn read_ini(path: &Path) -> (Option<String>, Option<String>) {
+    let text = match fs::read(path) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        Err(_) => return (None, None)
+    };
+    let (mut app_id, mut build_id) = (None, None);
+    for line in text.lines().map(|l| l.trim()) {
+        if line.starts_with(';') || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim().to_lowercase().replace([' ', '_'], ""), value.trim().trim_matches('"').to_string()),
+            None => continue
+        };
+        if !is_app_id(&value) {
+            continue;
+        }
+        match key.as_str() {
+            "appid" if app_id.is_none() => app_id = Some(value),
+            "buildid" if build_id.is_none() => build_id = Some(value),
+            _ => {}
+        }
+    }
+    (app_id, build_id)
+}
+
+// Look for everything that says what's installed in the game folder
+pub fn detect(game_dir: &Path) -> Option<Installed> {
+    let mut installed = Installed::default();
+
+    // What we installed ourselves, with the date of the release instead of the download
+    if let Some(manifest) = InstallManifest::from_dir(game_dir).filter(|m| is_app_id(&m.app_id)) {
+        installed.merge(Some(manifest.app_id), manifest.build_id, Some(manifest.released), &game_dir.join(INSTALL_MANIFEST));
+        installed.title = Some(manifest.title).filter(|t| !t.is_empty());
+        installed.variant = Some(manifest.variant);
+    }
+
+    if let Some((path, manifest)) = library_manifest(game_dir) {
+        installed.merge(Some(manifest.appid), manifest.buildid, manifest.last_updated, &path);
+    }
+
+    let mut found = Vec::new();
+    files(game_dir, 0, &mut found);
+    found.sort();
+
+    // A manifest that came with the release or was copied there
+    for path in found.iter().filter(|p| file_name(p).ends_with(".acf")) {
+        if let Some(manifest) = read_manifest(path) {
+            let date = manifest.last_updated.or_else(|| modified(path));
+            installed.merge(Some(manifest.appid), manifest.buildid, date, path);
+        }
+    }
+
+    for path in found.iter().filter(|p| file_name(p) == "steam_appid.txt") {
+        let app_id = fs::read_to_string(path).ok().map(|s| s.trim().to_string()).filter(|s| is_app_id(s));
+        installed.merge(app_id, None, modified(path), path);
+    }
+
+    for path in found.iter().filter(|p| EMULATOR_INIS.contains(&file_name(p).as_str())) {
+        let (app_id, build_id) = read_ini(path);
+        installed.merge(app_id, build_id, modified(path), path);
+    }
+
+    match installed.sources.is_empty() {
+        true => None,
+        false => Some(installed)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An empty folder in the temp dir for each test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rin_scraper_detect_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn manifest(app_id: &str, installdir: &str, build_id: &str) -> String {
+        format!("\"AppState\"\n{{\n\t\"appid\"\t\t\"{app_id}\"\n\t\"Universe\"\t\t\"1\"\n\t\"StateFlags\"\t\t\"4\"\n\t\"installdir\"\t\t\"{installdir}\"\n\t\"LastUpdated\"\t\t\"1666090000\"\n\t\"buildid\"\t\t\"{build_id}\"\n}}\n")
+    }
+
+    #[test]
+    fn reads_emulator_inis() {
+        let dir = temp_dir("ini");
+        let ini = dir.join("steam_emu.ini");
+        fs::write(&ini, "; AppId=1\n[Settings]\r\nApp_ID = 292030\r\nBuild ID=\"9755563\"\nAppId=70\nUserName=Player\n").unwrap();
+        assert_eq!(read_ini(&ini), (Some("292030".to_string()), Some("9755563".to_string())));
+
+        fs::write(&ini, "[Settings]\nAppId=unknown\nLanguage=english\n").unwrap();
+        assert_eq!(read_ini(&ini), (None, None));
+        assert_eq!(read_ini(&dir.join("missing.ini")), (None, None));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_the_library_manifest() {
+        let dir = temp_dir("library");
+        let steamapps = dir.join("steamapps");
+        let game_dir = steamapps.join("common").join("The Witcher 3");
+        fs::create_dir_all(&game_dir).unwrap();
+        fs::create_dir_all(steamapps.join("common").join("Portal")).unwrap();
+        fs::write(steamapps.join("appmanifest_400.acf"), manifest("400", "Portal", "5")).unwrap();
+        fs::write(steamapps.join("appmanifest_292030.acf"), manifest("292030", "The Witcher 3", "9755563")).unwrap();
+        fs::write(steamapps.join("libraryfolders.vdf"), "\"libraryfolders\"\n{\n}\n").unwrap();
+
+        let (path, found) = library_manifest(&game_dir).unwrap();
+        assert_eq!(path, steamapps.join("appmanifest_292030.acf"));
+        assert_eq!((found.appid.as_str(), found.buildid.as_deref(), found.last_updated), ("292030", Some("9755563"), Some(1666090000)));
+
+        // Only folders in steamapps/common are in a library
+        assert!(library_manifest(&steamapps).is_none());
+        assert!(library_manifest(&steamapps.join("common").join("Other")).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn install_manifest_comes_first() {
+        let dir = temp_dir("install");
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(dir.join("bin").join("steam_appid.txt"), "292030\n").unwrap();
+        fs::write(dir.join("bin").join("cpy.ini"), "[Settings]\nAppId=292030\nBuildId=9000000\n").unwrap();
+
+        let before = detect(&dir).unwrap();
+        assert_eq!((before.app_id.as_deref(), before.build_id.as_deref(), before.title), (Some("292030"), Some("9000000"), None));
+
+        let manifest = InstallManifest {
+            app_id: "292030".to_string(),
+            title: "The Witcher 3: Wild Hunt [Win64]".to_string(),
+            build_id: Some("9755563".to_string()),
+            variant: vec!["win64".to_string()],
+            released: 1666051200,
+            installed: 1666090000,
+            link: "https://privatebin.rinuploads.org/?a#b".to_string()
+        };
+        manifest.to_dir(&dir).unwrap();
+        assert_eq!(InstallManifest::from_dir(&dir), Some(manifest));
+
+        let after = detect(&dir).unwrap();
+        assert_eq!((after.app_id.as_deref(), after.build_id.as_deref()), (Some("292030"), Some("9755563")));
+        assert_eq!((after.installed, after.title.as_deref()), (Some(1666051200), Some("The Witcher 3: Wild Hunt [Win64]")));
+        assert_eq!(after.variant, Some(vec!["win64".to_string()]));
+        assert_eq!(after.sources[0], dir.join(INSTALL_MANIFEST));
+        assert_eq!(after.sources.len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,253 +1,466 @@
-use std::path::Path;
-use crate::scrape::{SteamInfo, SearchResult, LinkText, download_file};
-use terminal_link::Link;
-use chrono::{prelude::*};
-use terminal_size::{terminal_size, Width, Height};
-use std::path::PathBuf;
-use termimage;
-use image::GenericImageView;
-use crate::downloader::Update;
-use std::io::{stdin,stdout,Write};
-
-// 2 hyperlinks and lenghts of texts
-struct LinkPair {
-    link1: String,
-    len1: usize,
-    link2: String,
-    len2: usize
-}
-
-impl LinkPair {
-    fn new(one: String, two: String, len1: usize, len2: usize) -> LinkPair{
-        LinkPair { link1: one, link2: two, len1, len2 }
-    }
-    fn link1(&self) -> String { self.link1.clone() }
-    fn link2(&self) -> String { self.link2.clone() }
-    fn len1(&self) -> usize { self.len1.clone() }
-    fn len2(&self) -> usize { self.len2.clone() }
-
-
-}
-
-
-pub fn get_input(ask: &str) -> String {
-
-    let mut s=String::new();
-    print!("{}", ask);
-    let _=stdout().flush();
-    stdin().read_line(&mut s).expect("Did not enter a correct string");
-    if let Some('\n')=s.chars().next_back() {
-        s.pop();
-    }
-    if let Some('\r') = s.chars().next_back() {
-        s.pop();
-    }
-    s
-}
-
-// Convert epoch number to date in desired format
-pub fn epoch_to_date(time: String) -> String {
-
-    let timestamp = time.parse::<i64>().unwrap();
-    
-    
-    let naive = NaiveDateTime::from_timestamp(timestamp, 0);
-    
-
-    let datetime: DateTime<Utc> = DateTime::from_utc(naive, Utc);
-
-    datetime.format("%d.%m.%Y").to_string()
-}
-
-
-
-// Show image to the terminal
-pub async fn show_image(url: &str, tmp_dir: &Path) {
-    // Download the image
-    let image = download_file(&url, &tmp_dir).await; 
-
-    let term_image = (String::new(), PathBuf::from(image)); 
-    // Get format of the image
-    let format = termimage::ops::guess_format(&term_image).expect("Couldnt find the file format");
-
-    // Load the image to memory
-    let loaded_img = termimage::ops::load_image(&term_image, format).expect("Couldn't load the file");
-
-    // Get size of the terminal
-    let term_size: (u32, u32) = {
-        let size = terminal_size();
-        if let Some((Width(w), Height(h))) = size {
-            (w as u32, h as u32)
-        } else {
-            panic!("Unable to get terminal size")
-        }
-    };
-
-    // Create a resized image to fit it in the terminal
-    let img_s = termimage::ops::image_resized_size(loaded_img.dimensions(), term_size, true);
-    let resized = termimage::ops::resize_image(&loaded_img, img_s);
-
-
-    // Write it in true color
-    termimage::ops::write_ansi_truecolor(&mut std::io::stdout(), &resized);
-       
-
-}
-
-
-
-
-// Create a table line from two strings
-fn table_line(s1: String, l1: usize, s2: String, l2: usize, width: usize, wall: String) -> String {
-    
-    // Padding used for centering
-    let padding1 = (width - l1) / 2;
-    let padding2 = (width - l2) / 2;
-
-    // Prevention of wrong centering - i32 rounds to 0 in division
-    let padding1_2 = match (width - l1) % 2 == 0{
-        true => { padding1 },
-        false => { padding1 + 1}
-    };
-    let padding2_2 = match (width - l2) % 2 == 0{
-        true => { padding2 },
-        false => { padding2 + 1}
-    };
-    // | s1 | s2 |
-    let line = format!("{wall}{pad1}{s1}{pad1_2}{wall}{pad2}{s2}{pad2_2}{wall}",
-        pad1 = " ".repeat(padding1),
-        pad1_2 = " ".repeat(padding1_2),
-        pad2 = " ".repeat(padding2), 
-        pad2_2 = " ".repeat(padding2_2));
-
-    line // return the line
-} 
-// Create and print a table from steam and download links
-async fn link_table(steam_links: Vec<SteamInfo>, dl_links: Vec<LinkText>) {
-    // Pairs of steam and download links
-    let mut pairs: Vec<LinkPair> = Vec::new();
-
-    match steam_links.len() == dl_links.len() {
-        // If there is the same amount of links in both vectors ( most cases )
-        true => {
-            for i in 0..steam_links.len() {
-                let st_text = format!("{} | {}", &steam_links[i].title(), epoch_to_date(steam_links[i].last_update()));
-                let st_link = Link::new(&st_text, &steam_links[i].url()).to_string();
-                pairs.push(LinkPair::new(st_link, dl_links[i].to_hyper().to_string(), st_text.len(), dl_links[i].text().len()));
-            }
-        }
-
-        false => {// If not, check wich vector has more links and fill the other item in pair with " "
-            match steam_links.len() > dl_links.len() {
-                true => {
-                    for i in 0..dl_links.len() {
-                        let st_text = format!("{} | {}", &steam_links[i].title(), epoch_to_date(steam_links[i].last_update()));
-                        let st_link = Link::new(&st_text, &steam_links[i].url()).to_string();
-                        pairs.push(LinkPair::new(st_link, dl_links[i].to_hyper().to_string(), st_text.len(), dl_links[i].text().len()));
-
-                    }
-                    for i in dl_links.len()..steam_links.len() {
-                        let st_text = format!("{} | {}", &steam_links[i].title(), epoch_to_date(steam_links[i].last_update()));
-                        let st_link = Link::new(&st_text, &steam_links[i].url()).to_string();
-                        pairs.push(LinkPair::new(st_link, " ".to_string(), st_text.len(), " ".len()));
-                    }
-                }
-                false => {
-                    for i in 0..steam_links.len() {
-                        let st_text = format!("{} | {}", &steam_links[i].title(), epoch_to_date(steam_links[i].last_update()));
-                        let st_link = Link::new(&st_text, &steam_links[i].url()).to_string();
-                        pairs.push(LinkPair::new(st_link, dl_links[i].to_hyper().to_string(), st_text.len(), dl_links[i].text().len()));
-                    }
-                    for i in steam_links.len()..dl_links.len() {
-                        pairs.push(LinkPair::new(" ".to_string(), dl_links[i].to_hyper().to_string(), " ".len(), dl_links[i].text().len()));
-                    }
-                }
-            }
-        }
-    }
-
-    // Table Creation
-    let corner = "+".to_string();
-    let floor = "─".to_string();
-    let wall = "|".to_string();
-    let width = terminal_size().unwrap().0.0 as usize / 2 - 2;
-    // +───────+────────+
-    let pause = format!("{corner}{ceil}{corner}{ceil}{corner}", ceil = floor.to_string().repeat(width)); 
-    
-    // Table headers
-    let mut table = format!("{pause}\n{info}\n{pause}", 
-        info = table_line("Steam Links".to_string(), "Steam Links".len(), "Download Links".to_string(), "Download Links".len(), width, wall.clone())
-    );
-    // Createa a line for each pair
-    for pair in pairs {
-        table = format!("{table}\n{next_line}",
-        next_line = table_line(pair.link1(), pair.len1(), pair.link2(), pair.len2(), width, wall.clone())
-        );
-    }
-    // Add a 'floor' at the end
-    table = format!("{table}\n{pause}");
-
-    //Print it
-    println!("{}", table);
-
-
-}
-
-
-pub fn update_table(info: &Update) -> String{ // TODO: write a struct/funtion to create all tables instead of having two funcs for two tables
-     // Table Creation
-     let corner = "+".to_string();
-     let floor = "─".to_string();
-     let wall = "|".to_string();
-     let width = terminal_size().unwrap().0.0 as usize / 2 - 2;
-     // +───────+────────+
-     let pause = format!("{corner}{ceil}{corner}{ceil}{corner}", ceil = floor.to_string().repeat(width)); 
-
-      // Table headers
-    let mut table = format!("{pause}\n{info}\n{pause}", 
-    info = table_line("Current Version".to_string(), "Current Version".len(), "Available Version".to_string(), "Available Version".len(), width, wall.clone()));
-
-
-    let from = format!("{name} | {ver}", name = info.from().title(), ver = epoch_to_date(info.from().last_update()));
-    let to = format!("{name} | {ver}", name = info.to().title(), ver = epoch_to_date(info.to().last_update()));
-
-    table = format!("{table}\n{next_line}",
-        next_line = table_line(from.clone(), from.len(), to.clone(), to.len(), width, wall.clone())
-        );
-    
-    // Add a 'floor' at the end
-    table = format!("{table}\n{pause}");
-        
-
-    return table
-
-}
-
-
-pub fn center(text: &str, width: usize, len: usize) {
-    // text: the text to print out
-    // width: current width of the terminal
-    // len: lenght of the text that you want to center, used to center the hyperlinks
-    println!("{pad}{text}{pad}", pad = " ".repeat( ( ( width - len ) / 2 ) as usize ));
-}
-
-
-// Show info - this is the "main" function of file
-pub async fn show_info(info: &SearchResult, tmp_dir: &Path) -> Result<(), std::io::Error>{
-    let steam_links = info.steam_links();
-    let dl_links = info.dl_links();
-
-    let width = terminal_size().unwrap().0.0 as usize;
-    // Print the image
-    show_image(&info.img_url(), tmp_dir).await;
-    println!("");
-    center(&info.thread_info().to_hyper(), width, info.thread_info().text().len());
-    println!("");
-    link_table(steam_links, dl_links).await;
-
-
-
-
-    Ok(())
-}
-
+use std::path::Path;
+use crate::scrape::{SearchResult, LinkText, download_file};
+use crate::steam::SteamInfo;
+use crate::request::Requester;
+use terminal_link::Link;
+use chrono::{prelude::*};
+use terminal_size::{terminal_size, Width, Height};
+use termimage;
+use image::GenericImageView;
+use crate::downloader::{Update, Version};
+use crate::parser::TopicHit;
+use crate::applist::{App, AppList};
+use crate::dlc::{self, DlcStatus};
+use crate::freshness::{self, Freshness};
+use std::io::{stdin,stdout,Write};
+
+// 2 hyperlinks and lenghts of texts
+struct LinkPair {
+    link1: String,
+    len1: usize,
+    link2: String,
+    len2: usize
+}
+
+impl LinkPair {
+    fn new(one: String, two: String, len1: usize, len2: usize) -> LinkPair{
+        LinkPair { link1: one, link2: two, len1, len2 }
+    }
+    fn link1(&self) -> String { self.link1.clone() }
+    fn link2(&self) -> String { self.link2.clone() }
+    fn len1(&self) -> usize { self.len1.clone() }
+    fn len2(&self) -> usize { self.len2.clone() }
+
+
+}
+
+
+pub fn get_input(ask: &str) -> String {
+
+    let mut s=String::new();
+    print!("{}", ask);
+    let _=stdout().flush();
+    stdin().read_line(&mut s).expect("Did not enter a correct string");
+    if let Some('\n')=s.chars().next_back() {
+        s.pop();
+    }
+    if let Some('\r') = s.chars().next_back() {
+        s.pop();
+    }
+    s
+}
+
+// Convert epoch number to date in desired format
+pub fn epoch_to_date(time: String) -> String {
+
+    let timestamp = time.parse::<i64>().unwrap();
+    
+    
+    let naive = NaiveDateTime::from_timestamp(timestamp, 0);
+    
+
+    let datetime: DateTime<Utc> = DateTime::from_utc(naive, Utc);
+
+    datetime.format("%d.%m.%Y").to_string()
+}
+
+
+
+// Show image to the terminal
+pub async fn show_image(requests: &Requester, url: &str, tmp_dir: &Path) {
+    // Download the image, the info is still useful without it
+    let image = match download_file(requests, url, tmp_dir).await {
+        Ok(image) => image,
+        Err(e) => {
+            println!("Couldn't download the image: {}", e);
+            return
+        }
+    };
+
+    let term_image = (String::new(), image); 
+    // Get format of the image
+    let format = termimage::ops::guess_format(&term_image).expect("Couldnt find the file format");
+
+    // Load the image to memory
+    let loaded_img = termimage::ops::load_image(&term_image, format).expect("Couldn't load the file");
+
+    // Get size of the terminal
+    let term_size: (u32, u32) = {
+        let size = terminal_size();
+        if let Some((Width(w), Height(h))) = size {
+            (w as u32, h as u32)
+        } else {
+            panic!("Unable to get terminal size")
+        }
+    };
+
+    // Create a resized image to fit it in the terminal
+    let img_s = termimage::ops::image_resized_size(loaded_img.dimensions(), term_size, true);
+    let resized = termimage::ops::resize_image(&loaded_img, img_s);
+
+
+    // Write it in true color
+    termimage::ops::write_ansi_truecolor(&mut std::io::stdout(), &resized);
+       
+
+}
+
+
+
+
+// Create a table line from two strings
+fn table_line(s1: String, l1: usize, s2: String, l2: usize, width: usize, wall: String) -> String {
+    
+    // Padding used for centering, a cell wider than the column just isn't padded
+    let padding1 = width.saturating_sub(l1) / 2;
+    let padding2 = width.saturating_sub(l2) / 2;
+
+    // Prevention of wrong centering - i32 rounds to 0 in division
+    let padding1_2 = match width.saturating_sub(l1).is_multiple_of(2) {
+        true => { padding1 },
+        false => { padding1 + 1}
+    };
+    let padding2_2 = match width.saturating_sub(l2).is_multiple_of(2) {
+        true => { padding2 },
+        false => { padding2 + 1}
+    };
+    // | s1 | s2 |
+    let line = format!("{wall}{pad1}{s1}{pad1_2}{wall}{pad2}{s2}{pad2_2}{wall}",
+        pad1 = " ".repeat(padding1),
+        pad1_2 = " ".repeat(padding1_2),
+        pad2 = " ".repeat(padding2), 
+        pad2_2 = " ".repeat(padding2_2));
+
+    line // return the line
+} 
+
+// Cut a text to max characters, ending with … when something was cut
+fn shorten(text: &str, max: usize) -> String {
+    match text.chars().count() > max {
+        true => format!("{}…", text.chars().take(max.saturating_sub(1)).collect::<String>()),
+        false => text.to_string()
+    }
+}
+
+// Title, last update and public build of a steam app
+fn steam_text(steam: &SteamInfo) -> String {
+    match steam.build_id().is_empty() {
+        true => format!("{} | {}", steam.title(), epoch_to_date(steam.last_update())),
+        false => format!("{} | {} | Build {}", steam.title(), epoch_to_date(steam.last_update()), steam.build_id())
+    }
+}
+
+// Print the details of steam apps: state, platforms, depots and every branch with its build.
+// Public comes first and the rest from the newest, the followed branch has a *
+fn steam_details(steam_links: &[SteamInfo], followed: &str) {
+    for steam in steam_links {
+        println!("{} ({}): {}, {}, {} depots", steam.title(), steam.app_id(), steam.release_state(), steam.os_list().join("/"), steam.depots().len());
+        let mut branches = steam.branches();
+        branches.sort_by_key(|b| (b.name() != "public", -b.time_updated().parse::<i64>().unwrap_or(0)));
+        for branch in branches {
+            let kind = match (branch.name() == "public", branch.pwd_required()) {
+                (true, _) => "public",
+                (false, false) => "beta",
+                (false, true) => "beta, password"
+            };
+            let mark = if branch.name() == followed { "*" } else { " " };
+            println!(" {}{}: build {} ({}) [{}]", mark, branch.name(), branch.build_id(), epoch_to_date(branch.time_updated()), kind);
+        }
+    }
+}
+
+// Print which DLCs of the games are in the topic
+fn dlc_details(info: &SearchResult, apps: Option<&AppList>) {
+    for steam in info.steam_links().iter().filter(|s| !s.dlc().is_empty()) {
+        let mut dlcs = dlc::coverage(info, steam, apps);
+        let count = |status: DlcStatus| dlcs.iter().filter(|d| d.status() == status).count();
+        println!("{} DLCs: {} included, {} missing, {} unknown", steam.title(), count(DlcStatus::Included), count(DlcStatus::Missing), count(DlcStatus::Unknown));
+        // Included first, then missing and unknown
+        dlcs.sort_by_key(|d| match d.status() { DlcStatus::Included => 0, DlcStatus::Missing => 1, DlcStatus::Unknown => 2 });
+        for dlc in dlcs {
+            let name = dlc.name().unwrap_or_else(|| "?".to_string());
+            println!("  {} ({}): {}", name, dlc.app_id(), dlc.status().colored());
+        }
+    }
+}
+
+// Create and print a table from steam and download links
+async fn link_table(steam_links: Vec<SteamInfo>, dl_links: Vec<LinkText>, freshness: Vec<Freshness>) {
+    let width = terminal_size().unwrap().0.0 as usize / 2 - 2;
+    println!("{}", links_text(&steam_links, &dl_links, &freshness, width));
+}
+
+// The table of steam and download links, long texts are cut to fit the columns
+fn links_text(steam_links: &[SteamInfo], dl_links: &[LinkText], freshness: &[Freshness], width: usize) -> String {
+    // Pairs of steam and download links
+    let mut pairs: Vec<LinkPair> = Vec::new();
+    // Text of a cell can use the width without the 1 space margin on both sides
+    let room = width.saturating_sub(2);
+    // Steam links with the title, update and build, and the length of the visible text
+    let st_cells: Vec<(String, usize)> = steam_links.iter()
+        .map(|steam| {
+            let text = shorten(&steam_text(steam), room);
+            (Link::new(&text, &steam.url()).to_string(), text.chars().count())
+        })
+        .collect();
+    // Download links with what they are, how fresh they are, and the length of the visible text.
+    // The link text is cut first so the label and the status stay visible
+    let dl_cells: Vec<(String, usize)> = dl_links.iter().zip(freshness.iter())
+        .map(|(dl, status)| {
+            let mut suffix = (String::new(), 0);
+            if let Some(label) = dl.kind().and_then(|k| k.label().map(|l| format!("[{}]", l))) {
+                suffix.0.push_str(&format!(" {}", label));
+                suffix.1 += 1 + label.len();
+            }
+            if *status != Freshness::Unknown {
+                suffix.0.push_str(&format!(" {}", status.colored()));
+                suffix.1 += 1 + status.to_string().len();
+            }
+            let text = shorten(&dl.text(), room.saturating_sub(suffix.1).max(1));
+            (format!("{}{}", Link::new(&text, &dl.link()), suffix.0), text.chars().count() + suffix.1)
+        })
+        .collect();
+
+    match st_cells.len() == dl_cells.len() {
+        // If there is the same amount of links in both vectors ( most cases )
+        true => {
+            for i in 0..st_cells.len() {
+                pairs.push(LinkPair::new(st_cells[i].0.clone(), dl_cells[i].0.clone(), st_cells[i].1, dl_cells[i].1));
+            }
+        }
+
+        false => {// If not, check wich vector has more links and fill the other item in pair with " "
+            match st_cells.len() > dl_cells.len() {
+                true => {
+                    for i in 0..dl_cells.len() {
+                        pairs.push(LinkPair::new(st_cells[i].0.clone(), dl_cells[i].0.clone(), st_cells[i].1, dl_cells[i].1));
+
+                    }
+                    for (cell, len) in &st_cells[dl_cells.len()..] {
+                        pairs.push(LinkPair::new(cell.clone(), " ".to_string(), *len, " ".len()));
+                    }
+                }
+                false => {
+                    for i in 0..st_cells.len() {
+                        pairs.push(LinkPair::new(st_cells[i].0.clone(), dl_cells[i].0.clone(), st_cells[i].1, dl_cells[i].1));
+                    }
+                    for (cell, len) in &dl_cells[st_cells.len()..] {
+                        pairs.push(LinkPair::new(" ".to_string(), cell.clone(), " ".len(), *len));
+                    }
+                }
+            }
+        }
+    }
+
+    // Table Creation
+    let corner = "+".to_string();
+    let floor = "─".to_string();
+    let wall = "|".to_string();
+    // +───────+────────+
+    let pause = format!("{corner}{ceil}{corner}{ceil}{corner}", ceil = floor.to_string().repeat(width)); 
+    
+    // Table headers
+    let mut table = format!("{pause}\n{info}\n{pause}", 
+        info = table_line("Steam Links".to_string(), "Steam Links".len(), "Download Links".to_string(), "Download Links".len(), width, wall.clone())
+    );
+    // Createa a line for each pair
+    for pair in pairs {
+        table = format!("{table}\n{next_line}",
+        next_line = table_line(pair.link1(), pair.len1(), pair.link2(), pair.len2(), width, wall.clone())
+        );
+    }
+    // Add a 'floor' at the end
+    table = format!("{table}\n{pause}");
+
+    table
+}
+
+
+pub fn update_table(info: &Update) -> String{ // TODO: write a struct/funtion to create all tables instead of having two funcs for two tables
+     // Table Creation
+     let corner = "+".to_string();
+     let floor = "─".to_string();
+     let wall = "|".to_string();
+     let width = terminal_size().unwrap().0.0 as usize / 2 - 2;
+     // +───────+────────+
+     let pause = format!("{corner}{ceil}{corner}{ceil}{corner}", ceil = floor.to_string().repeat(width)); 
+
+      // Table headers
+    let mut table = format!("{pause}\n{info}\n{pause}", 
+    info = table_line("Current Version".to_string(), "Current Version".len(), "Available Version".to_string(), "Available Version".len(), width, wall.clone()));
+
+
+    let version = |v: &Version| match v.build_id() {
+        Some(build) => format!("{name} | {ver} | Build {build}", name = v.title(), ver = epoch_to_date(v.last_update())),
+        None => format!("{name} | {ver}", name = v.title(), ver = epoch_to_date(v.last_update()))
+    };
+    let from = version(&info.from());
+    let to = version(&info.to());
+
+    table = format!("{table}\n{next_line}",
+        next_line = table_line(from.clone(), from.len(), to.clone(), to.len(), width, wall.clone())
+        );
+    // What Steam has now, to see if the update is the latest
+    if let Some(build) = info.steam_build() {
+        let steam = format!("Steam ({}): Build {}", info.branch(), build);
+        table = format!("{table}\n{next_line}",
+            next_line = table_line(" ".to_string(), 1, steam.clone(), steam.len(), width, wall.clone())
+            );
+    }
+    
+    // Add a 'floor' at the end
+    table = format!("{table}\n{pause}");
+        
+
+    return table
+
+}
+
+
+pub fn center(text: &str, width: usize, len: usize) {
+    // text: the text to print out
+    // width: current width of the terminal
+    // len: lenght of the text that you want to center, used to center the hyperlinks
+    println!("{pad}{text}{pad}", pad = " ".repeat(width.saturating_sub(len) / 2));
+}
+
+
+// List the topics found and let the user pick one, returns its index
+pub fn select_topic(hits: &[TopicHit]) -> usize {
+    let txt = format!("Found {} topics", hits.len());
+    center(&txt, terminal_size().unwrap().0.0.into(), txt.len());
+    println!();
+
+    for (i, hit) in hits.iter().enumerate() {
+        let title = Link::new(&hit.title(), &hit.href()).to_string();
+        println!("{:>3}. {title} | by {} | {} replies | {} views | {}", i + 1, hit.author(), hit.replies(), hit.views(), hit.last_post());
+    }
+    println!();
+
+    loop {
+        let ans = get_input(&format!("Select a topic (1-{}): ", hits.len()));
+        match ans.trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= hits.len() => { return n - 1 },
+            _ => { println!("Invalid response: '{ans}', try again.") }
+        }
+    }
+}
+
+
+// Let the user pick one of the apps that matched a name, None to search for the name as it is
+pub fn select_app(query: &str, apps: &[App]) -> Option<App> {
+    println!("'{query}' could be more than one game:");
+    for (i, app) in apps.iter().enumerate() {
+        println!("{:>3}. {} ({})", i + 1, app.name, app.appid);
+    }
+    println!("  0. Search for '{query}'");
+    println!();
+
+    loop {
+        let ans = get_input(&format!("Select a game (0-{}): ", apps.len()));
+        match ans.trim().parse::<usize>() {
+            Ok(0) => { return None },
+            Ok(n) if n <= apps.len() => { return Some(apps[n - 1].clone()) },
+            _ => { println!("Invalid response: '{ans}', try again.") }
+        }
+    }
+}
+
+
+// Show info - this is the "main" function of file
+pub async fn show_info(info: &SearchResult, tmp_dir: &Path, requests: &Requester, apps: Option<&AppList>, branch: &str) -> Result<(), std::io::Error>{
+    let steam_links = info.steam_links();
+    let dl_links = info.dl_links();
+
+    let width = terminal_size().unwrap().0.0 as usize;
+    // Print the image if the post has one
+    if let Some(img_url) = info.img_url() {
+        show_image(requests, &img_url, tmp_dir).await;
+    }
+    println!("");
+    center(&info.thread_info().to_hyper(), width, info.thread_info().text().len());
+    println!("");
+    link_table(steam_links.clone(), dl_links.clone(), freshness::report(info, branch)).await;
+    steam_details(&steam_links, branch);
+    dlc_details(info, apps);
+
+    // Links that were posted in replies, with who posted them and when
+    let posts = info.posts();
+    let first_post = posts.first().map(|p| p.post_id());
+    for link in dl_links.iter().filter(|l| l.source().is_some() && l.source() != first_post) {
+        if let Some(post) = posts.iter().find(|p| Some(p.post_id()) == link.source()) {
+            println!("{} - posted by {} on {}", link.to_hyper(), post.author(), post.posted());
+        }
+    }
+
+
+
+
+    Ok(())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // The text as it shows in the terminal, without hyperlinks and colors
+    fn visible(line: &str) -> String {
+        let mut text = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                // OSC, ends with ESC \
+                ('\u{1b}', Some(']')) => {
+                    while let Some(c) = chars.next() {
+                        if c == '\u{1b}' && chars.next() == Some('\\') { break }
+                    }
+                }
+                // CSI, ends with a letter
+                ('\u{1b}', Some('[')) => {
+                    for c in chars.by_ref().skip(1) {
+                        if c.is_ascii_alphabetic() { break }
+                    }
+                }
+                _ => text.push(c)
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn long_cells_fit_the_table() {
+        let json = json!({ "data": { "292030": {
+            "common": { "name": "The Witcher 3: Wild Hunt - Game of the Year Edition" },
+            "depots": { "branches": { "public": { "buildid": "9755563", "timeupdated": "1666083660" } } }
+        } } });
+        let steam = SteamInfo::from_steamcmd("292030", &json).unwrap();
+        let dl = LinkText::new("https://example.com/witcher", "The Witcher 3: Wild Hunt - Game of the Year Edition v4.04");
+
+        let table = links_text(&[steam.clone(), steam], &[dl], &[Freshness::Behind(3)], 38);
+        let lines: Vec<String> = table.lines().map(visible).collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines.iter().all(|l| l.chars().count() == 2 * 38 + 3), "{:#?}", lines);
+        // The link text is cut, what it is and how fresh it is are kept
+        assert_eq!(lines[3], "| The Witcher 3: Wild Hunt - Game of … | The Witcher 3: … 3 days behind Steam |");
+        assert_eq!(lines[4], "| The Witcher 3: Wild Hunt - Game of … |                                      |");
+    }
+
+    #[test]
+    fn long_download_cells_fit_the_table() {
+        let dl = LinkText::new("https://example.com/witcher", "The Witcher 3: Wild Hunt - Game of the Year Edition v4.04");
+        let lines: Vec<String> = links_text(&[], &[dl], &[Freshness::Newer], 38).lines().map(visible).collect();
+        assert!(lines.iter().all(|l| l.chars().count() == 2 * 38 + 3), "{:#?}", lines);
+        // The link text is cut, how fresh it is is kept
+        assert_eq!(lines[3], "|                                      |… newer than the public branch (beta?)|");
+    }
+
+    #[test]
+    fn table_line_overflows_without_padding() {
+        let long = "x".repeat(50);
+        assert_eq!(table_line(long.clone(), long.len(), "y".to_string(), 1, 38, "|".to_string()),
+            format!("|{long}|{}y{}|", " ".repeat(18), " ".repeat(19)));
+    }
+}
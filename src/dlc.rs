@@ -0,0 +1,152 @@
+use std::fmt;
+
+use crate::applist::{AppList, normalize};
+use crate::scrape::SearchResult;
+use crate::steam::SteamInfo;
+
+// Which DLCs of a game the topic has. Posts on SCS list the included DLCs by name,
+// so the names from the Steam app list are looked for in the first post and the release titles
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DlcStatus {
+    Included,
+    Missing, // the post lists DLCs, but not this one
+    Unknown // the post doesn't say
+}
+
+impl DlcStatus {
+    pub fn colored(&self) -> String {
+        match self {
+            DlcStatus::Included => format!("\x1B[32m{}\x1B[0m", self),
+            DlcStatus::Missing => format!("\x1B[31m{}\x1B[0m", self),
+            DlcStatus::Unknown => self.to_string()
+        }
+    }
+}
+
+impl fmt::Display for DlcStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DlcStatus::Included => write!(f, "included"),
+            DlcStatus::Missing => write!(f, "missing"),
+            DlcStatus::Unknown => write!(f, "unknown")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dlc {
+    app_id: String,
+    name: Option<String>,
+    status: DlcStatus
+}
+
+impl Dlc {
+    pub fn app_id(&self) -> String { self.app_id.clone() }
+    pub fn name(&self) -> Option<String> { self.name.clone() }
+    pub fn status(&self) -> DlcStatus { self.status.clone() }
+}
+
+// Text the DLCs are looked for in: the first post with its links, and the titles of the releases
+fn post_text(res: &SearchResult) -> String {
+    let mut text = String::new();
+    if let Some(post) = res.posts().first() {
+        text.push_str(&post.text());
+        for link in post.links() {
+            text.push(' ');
+            text.push_str(&link.link());
+        }
+    }
+    for link in res.dl_links() {
+        text.push(' ');
+        text.push_str(&link.text());
+    }
+    text
+}
+
+// " words " of the text, so phrases only match whole words
+fn padded(text: &str) -> String {
+    format!(" {} ", normalize(text))
+}
+
+// Name of the DLC without the name of the game, "Game - Soundtrack" is usually just "Soundtrack" in posts
+fn short_name(name: &str, game: &str) -> String {
+    let name = normalize(name);
+    let game = normalize(game);
+    match name.strip_prefix(&game) {
+        Some(rest) if !rest.trim().is_empty() => rest.trim().to_string(),
+        _ => name
+    }
+}
+
+// Status of every DLC of the game. Without the app list only the app ids can be matched
+pub fn coverage(res: &SearchResult, game: &SteamInfo, apps: Option<&AppList>) -> Vec<Dlc> {
+    let text = padded(&post_text(res));
+    let lists_dlc = text.contains(" dlc ") || text.contains(" dlcs ");
+    let all_dlc = text.contains(" all dlc ") || text.contains(" all dlcs ");
+
+    game.dlc().into_iter()
+        .map(|app_id| {
+            let name = apps.and_then(|apps| apps.name(&app_id));
+            let mentioned = text.contains(&format!(" {} ", app_id))
+                || name.as_ref().map(|n| {
+                    let short = short_name(n, &game.title());
+                    !short.is_empty() && text.contains(&format!(" {} ", short))
+                }).unwrap_or(false);
+
+            let status = match (mentioned || all_dlc, lists_dlc, &name) {
+                (true, _, _) => DlcStatus::Included,
+                (false, true, Some(_)) => DlcStatus::Missing,
+                _ => DlcStatus::Unknown
+            };
+            Dlc { app_id, name, status }
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosts::HostRegistry;
+    use crate::parser;
+    use crate::scrape::LinkText;
+
+    fn topic(post: &str) -> SearchResult {
+        let posts = parser::posts(&format!(r#"<html><body><a name="p1"></a><div class="postbody">{post}</div></body></html>"#));
+        let mut res = SearchResult::new(LinkText::new("https://cs.rin.ru/forum/viewtopic.php?t=63410", "The Witcher 3: Wild Hunt"), String::new(), None, Vec::new(), Vec::new());
+        res.merge_posts(&posts, &HostRegistry::defaults());
+        res
+    }
+
+    // The Witcher 3 has the DLCs 355880 (not in the app list), Blood and Wine (378648) and Hearts of Stone (378649)
+    fn coverage_of(post: &str, apps: Option<&AppList>) -> Vec<(String, DlcStatus)> {
+        let json = serde_json::from_str(include_str!("../tests/fixtures/steam_metadata.json")).unwrap();
+        let game = SteamInfo::from_steamcmd("292030", &json).unwrap();
+        coverage(&topic(post), &game, apps).into_iter().map(|d| (d.app_id(), d.status())).collect()
+    }
+
+    fn statuses(unlisted: DlcStatus, blood_and_wine: DlcStatus, hearts_of_stone: DlcStatus) -> Vec<(String, DlcStatus)> {
+        vec![("355880".to_string(), unlisted), ("378648".to_string(), blood_and_wine), ("378649".to_string(), hearts_of_stone)]
+    }
+
+    #[test]
+    fn matches_dlc_names() {
+        let apps = AppList::parse(include_bytes!("../tests/fixtures/applist.json")).unwrap();
+        let apps = Some(&apps);
+        use DlcStatus::*;
+
+        // Without the name of the game, and not a part of another word
+        assert_eq!(coverage_of("The Witcher 3 | 18.10.2022<br />Included DLC: Blood and Wine", apps), statuses(Unknown, Included, Missing));
+        assert_eq!(coverage_of("DLCs: The Witcher 3: Wild Hunt - Hearts of Stone, 355880", apps), statuses(Included, Missing, Included));
+        assert_eq!(coverage_of("All DLCs included", apps), statuses(Included, Included, Included));
+        // A post that doesn't list DLCs doesn't say they're missing
+        assert_eq!(coverage_of("Blood and Wine soon", apps), statuses(Unknown, Included, Unknown));
+        assert_eq!(coverage_of("Just the game", apps), statuses(Unknown, Unknown, Unknown));
+        assert_eq!(coverage_of("DLC: Hearts of Stoneware", apps), statuses(Unknown, Missing, Missing));
+
+        // Without the app list only app ids are found
+        assert_eq!(coverage_of("DLC: Blood and Wine, 378649", None), statuses(Unknown, Unknown, Included));
+    }
+}
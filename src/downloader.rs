@@ -1,198 +1,323 @@
-use regex::Regex;
-use terminal_size::terminal_size;
-use thirtyfour::{WebDriver, By};
-use crate::{scrape::SearchResult, config::{AppConfig, get_jd_path}, display::{center, update_table, epoch_to_date, get_input}};
-use chrono::NaiveDate;
-use std::{process, time::Duration};
-use std::thread;
-
-
-// Version Struct
-#[derive(Clone)]
-pub struct Version {
-	title: String,
-	last_update: String
-}
-impl Version {
-	pub fn title(&self) -> String { self.title.clone() }
-	pub fn last_update(&self) -> String { self.last_update.clone() }
-
-}
-
-// Update Struct
-
-pub struct Update {
-	from: Version,
-	to: Version
-}
-impl Update {
-	pub fn from(&self) -> Version { self.from.clone() }
-	pub fn to(&self) -> Version { self.to.clone() }
-
-}
-
-// Convert date from dd.mm.YYYY to epoch 
-fn date_to_epoch(date: String) -> String {
-	let datetime = NaiveDate::parse_from_str(&date, "%d.%m.%Y").unwrap();
-	let epoch = datetime.and_hms(0, 0, 0).timestamp().to_string();
-	epoch
-}
-
-// Get links from privatebin
-async fn get_privatebin(link: String, driver: &WebDriver) -> Vec<String> {
-	let mut v: Vec<String> = Vec::new();
-
-	driver.goto(link).await.unwrap();
-	thread::sleep(Duration::from_secs_f32(1.0));
-
-	let input = driver.find(By::Id("passworddecrypt")).await.unwrap();
-	let mut button_opt = driver.find(By::XPath("/html/body/div[1]/div/div/div/form/button")).await;
-
-    while match button_opt {
-                Ok(_) => false,
-                Err(_) => true} {
-                    thread::sleep(Duration::from_secs_f32(0.2));
-                    button_opt = driver.find(By::Name("btn btn-success btn-block")).await;
-                }
-
-	let button = button_opt.unwrap();
-	input.send_keys("cs.rin.ru").await.unwrap();
-	button.click().await.unwrap();
-
-	let mut text_opt = driver.find(By::XPath(r#"//*[@id="prettyprint"]"#)).await;
-
-    while match text_opt {
-                Ok(_) => false,
-                Err(_) => true} {
-                    thread::sleep(Duration::from_secs_f32(0.2));
-                    text_opt = driver.find(By::XPath(r#"//*[@id="prettyprint"]"#)).await;
-                }
-
-	let text = text_opt.unwrap();
-
-
-	let links = text.find_all(By::Tag("a")).await.unwrap();
-
-	
-
-	for i in links {
-		v.push(i.attr("href").await.unwrap_or(Some("".to_string())).unwrap());
-	}
-
-	v
-}
-
-// Check if link with last game title has changed last update date and create an Update Struct if so
-pub fn check_update(sr: &SearchResult, cfg: &AppConfig) -> Option<Update> {
-
-    let last_title = cfg.last_update_title();
-	let last_date = date_to_epoch(cfg.last_update_str());
-
-	let re = regex::Regex::new(r#"(?P<title>[[:ascii:]]+) \| (?P<date>[[:digit:]]{2}\.[[:digit:]]{2}\.[[:digit:]]{4})"#).unwrap();
-
-	for i in sr.dl_links() {
-		let text = i.text();
-
-		let result = re.captures(&text).unwrap();
-		let title_match =  result.name("title");
-		let title = match title_match {
-			Some(_) => { title_match.unwrap().as_str().to_string() }
-			None => {"".to_string() } 
-		};
-
-		let date_match =  result.name("date");
-		let date = match date_match {
-			Some(_) => { date_to_epoch(date_match.unwrap().as_str().to_string()) }
-			None => { "".to_string() }
-		};
-		if title == last_title && date != last_date {
-			return Some(
-				Update{
-					from: Version { title: last_title, last_update: last_date },
-					to: Version { title: title, last_update: date }
-				}
-			);
-		}
-
-	}
-	return None;
-	
-}
-
-
-// Ask user about updating the game
-pub fn ask_update(info: &Update) -> bool{
-	let txt = "There's an Update Available";
-	center(txt, terminal_size::terminal_size().unwrap().0.0.into(), txt.len());
-	println!("");
-	println!("{}", update_table(info));
-
-	loop {
-		let ans = get_input("Do you want to download the update? y/n: ");
-		match ans.to_lowercase().as_str() {
-			"y" => { return true },
-			"n" => { return false },
-			s => { println!("Invalid response: '{s}', try again.")}
-		}
-	}
-}
-// Parse and download links, currently using JDownloader 2
-pub async fn download_update(res: &SearchResult, update: &Update, cfg: &mut AppConfig, driver: &WebDriver) {
-	let dl_title = format!("{} | {}", update.to().title(), epoch_to_date(update.to().last_update()));
-	println!("Updating: {}", dl_title);
-	let mut dl_link = "".to_string();
-
-	for link in res.dl_links() {
-		if link.text() == dl_title {
-			dl_link = link.link();
-		}
-	}
-
-
-
-	let unsupported = vec![
-		"filecrypt.cc"
-	];
-	let domain_re = Regex::new(r"(?i)^(?:https?://)?(?:[^@/\n]+@)?(?:www\.)?([^:/?\n]+)").unwrap();
-
-
-
-	let domain_opt = domain_re.captures(&dl_link);
-	let domain = match domain_opt {
-		None => {
-			let text = format!("Invalid link: {}", dl_link);
-			center(&text, terminal_size().unwrap().0.0.into(), text.len());
-			return ()
-		}
-		Some(x) => x.get(1).unwrap().as_str()
-	};
-	let mut dl_links: Vec<String> = Vec::new();
-	if unsupported.contains(&domain) {
-		println!("We don't support {domain} at this time, here's the link: {dl_link}");
-		return ()
-	}
-	else if domain == "privatebin.rinuploads.org" {
-		dl_links = get_privatebin(dl_link, driver).await;
-	}
-	else {
-		dl_links = vec![dl_link];
-	}
-	
-	
-	dl_links.insert(0, "-add-link".to_string());
-	
-	let jd = get_jd_path();
-
-	let _ = process::Command::new(jd)
-		.args(dl_links)
-		.spawn()
-		.unwrap();
-
-	cfg.last_update_title = update.to().title();
-	cfg.last_update = update.to().last_update();
-	cfg.last_update_str = epoch_to_date(update.to().last_update());
-
-
-
-
-}
\ No newline at end of file
+use terminal_size::terminal_size;
+use crate::{scrape::{SearchResult, LinkText}, release, hosts::{HostRegistry, LinkKind, Support, Resolver}, backend::{ForumBackend, BackendResult, wait_for}, privatebin, config::{AppConfig, get_jd_path}, detect::InstallManifest, display::{center, update_table, epoch_to_date, get_input}};
+use std::{cmp::Ordering, process, time::Duration};
+
+
+// Version Struct
+#[derive(Clone)]
+pub struct Version {
+	title: String,
+	last_update: String,
+	build_id: Option<String>, // Steam build, if the release says which one it is
+	variant: Option<Vec<String>> // platform and tags
+}
+impl Version {
+	pub fn title(&self) -> String { self.title.clone() }
+	pub fn last_update(&self) -> String { self.last_update.clone() }
+	pub fn build_id(&self) -> Option<String> { self.build_id.clone() }
+	pub fn variant(&self) -> Option<Vec<String>> { self.variant.clone() }
+
+}
+
+// Update Struct
+
+pub struct Update {
+	from: Version,
+	to: Version,
+	steam_build: Option<String>, // current build of the followed branch
+	branch: String,
+	mirrors: Vec<LinkText> // links of the new release, in the order they're tried
+}
+impl Update {
+	pub fn from(&self) -> Version { self.from.clone() }
+	pub fn to(&self) -> Version { self.to.clone() }
+	pub fn steam_build(&self) -> Option<String> { self.steam_build.clone() }
+	pub fn branch(&self) -> String { self.branch.clone() }
+	pub fn mirrors(&self) -> Vec<LinkText> { self.mirrors.clone() }
+
+}
+
+// Get links from privatebin
+async fn get_privatebin(link: String, backend: &mut dyn ForumBackend) -> BackendResult<Vec<String>> {
+	// Without javascript the paste has to be decrypted here
+	if !backend.runs_scripts() {
+		return Ok(privatebin::get_links(&backend.requests(), &link, privatebin::PASTE_PASSWORD).await?);
+	}
+
+	backend.navigate(&link).await?;
+
+	wait_for(backend, "#passworddecrypt", Duration::from_secs(30)).await?;
+	backend.submit_form(&[("#passworddecrypt", privatebin::PASTE_PASSWORD)], "button.btn-success").await?;
+
+	wait_for(backend, "#prettyprint a", Duration::from_secs(30)).await?;
+	let links = backend.find_all("#prettyprint a").await?;
+
+	// Replays decrypt the paste without a browser, so the paste itself has to be in the recording too
+	if backend.requests().is_recording() {
+		if let Err(e) = privatebin::get_paste(&backend.requests(), &link, privatebin::PASTE_PASSWORD).await {
+			eprintln!("[record] Couldn't save the paste {}: {}", link, e);
+		}
+	}
+
+	Ok(links.iter().map(|i| i.attr("href").unwrap_or_default()).collect())
+}
+
+// Check if link with last game title has changed last update date and create an Update Struct if so
+pub fn check_update(sr: &SearchResult, cfg: &AppConfig, hosts: &HostRegistry) -> Option<Update> {
+
+    let last_title = cfg.last_update_title();
+	// The config has the date written out too, the epoch is used if it can't be read
+	let last_date = release::parse_date(&cfg.last_update_str()).map(|d| d.to_string()).unwrap_or_else(|| cfg.last_update());
+	let last_build = cfg.build_id.as_ref().and_then(|b| b.parse::<u64>().ok());
+
+	let steam_links = sr.steam_links();
+	let steam_build = steam_links.iter()
+		.find(|s| s.app_id() == cfg.app_id())
+		.or_else(|| steam_links.first())
+		.and_then(|s| s.branch(&cfg.branch()).map(|b| b.build_id()).or_else(|| Some(s.build_id())))
+		.filter(|b| !b.is_empty());
+
+	let mut newest: Option<release::Release> = None;
+	// Links from replies don't always follow the format, those aren't releases
+	for release in release::releases(sr, hosts) {
+		// Builds are exact when both are known, otherwise only releases after the installed version count,
+		// it may have been installed after its release
+		let newer = match (release.build_id, last_build) {
+			(Some(build), Some(last_build)) => build > last_build,
+			_ => release.date.unwrap_or(0) > last_date.parse::<i64>().unwrap_or(0)
+		};
+		// Replies can have more than one update since then, the newest is offered
+		let newest_yet = newest.as_ref().map(|n| release::compare(&release, n) == Some(Ordering::Greater)).unwrap_or(true);
+		if release.follows(&last_title, cfg.variant.as_deref()) && newer && newest_yet {
+			newest = Some(release);
+		}
+	}
+
+	newest.map(|release| Update {
+		from: Version { title: last_title, last_update: last_date, build_id: cfg.build_id.clone(), variant: cfg.variant.clone() },
+		to: Version { title: release.heading.clone(), last_update: release.date.unwrap_or(0).to_string(), build_id: release.build_id.map(|b| b.to_string()), variant: Some(release.variant()) },
+		steam_build,
+		branch: cfg.branch(),
+		mirrors: release.mirrors
+	})
+}
+
+
+// Ask user about updating the game
+pub fn ask_update(info: &Update) -> bool{
+	let txt = "There's an Update Available";
+	center(txt, terminal_size::terminal_size().unwrap().0.0.into(), txt.len());
+	println!("");
+	println!("{}", update_table(info));
+
+	loop {
+		let ans = get_input("Do you want to download the update? y/n: ");
+		match ans.to_lowercase().as_str() {
+			"y" => { return true },
+			"n" => { return false },
+			s => { println!("Invalid response: '{s}', try again.")}
+		}
+	}
+}
+// Links JDownloader gets for a mirror, or why we can't download from it
+async fn resolve(mirror: &LinkText, backend: &mut dyn ForumBackend, hosts: &HostRegistry) -> Result<Vec<String>, String> {
+	let dl_link = mirror.link();
+
+	// JDownloader can't download torrents
+	if let Some(kind) = mirror.kind().filter(|k| k.is_torrent()) {
+		return Err(format!("This mirror is a {}, open it in your torrent client: {dl_link}", kind.label().unwrap_or("torrent")));
+	}
+
+	let (domain, host) = match hosts.lookup(&dl_link) {
+		Some(host) => host,
+		None => return Err(format!("Invalid link: {}", dl_link))
+	};
+	if host.support != Support::Supported {
+		let why = if host.kind == LinkKind::Container { " (the links are behind a captcha)" } else { "" };
+		return Err(format!("We don't support {domain} at this time{why}, here's the link: {dl_link}"));
+	}
+	match host.resolver {
+		Resolver::PrivateBin => match get_privatebin(dl_link.clone(), backend).await {
+			// Nothing for JDownloader to add, the next mirror may have the files
+			Ok(links) if links.is_empty() => Err(format!("The PrivateBin paste has no links, here's the link: {dl_link}")),
+			res => res.map_err(|e| format!("Couldn't get the links from PrivateBin ({e}), here's the link: {dl_link}"))
+		},
+		Resolver::Direct => Ok(vec![dl_link])
+	}
+}
+
+// The first mirror with links JDownloader can get, and the links.
+// The next mirror is tried when one can't be downloaded from
+async fn first_mirror(mirrors: &[LinkText], backend: &mut dyn ForumBackend, hosts: &HostRegistry) -> Option<(LinkText, Vec<String>)> {
+	for mirror in mirrors {
+		match resolve(mirror, backend, hosts).await {
+			Ok(links) => return Some((mirror.clone(), links)),
+			Err(why) => println!("{why}")
+		}
+	}
+	None
+}
+
+// Parse and download links, currently using JDownloader 2
+pub async fn download_update(update: &Update, cfg: &mut AppConfig, backend: &mut dyn ForumBackend, hosts: &HostRegistry) {
+	let dl_title = format!("{} | {}", update.to().title(), epoch_to_date(update.to().last_update()));
+	println!("Updating: {}", dl_title);
+
+	let (mirror, mut dl_links) = match first_mirror(&update.mirrors(), backend, hosts).await {
+		Some(found) => found,
+		None => {
+			let text = "No mirror could be downloaded from";
+			center(text, terminal_size().unwrap().0.0.into(), text.len());
+			return
+		}
+	};
+
+	// JDownloader doesn't take a proxy from the command line
+	let proxy = backend.requests().proxy();
+	let proxied = dl_links.iter()
+		.filter_map(|l| reqwest::Url::parse(l).ok())
+		.any(|u| u.host_str().and_then(|h| proxy.for_host(h)).is_some());
+	if proxied {
+		println!("A proxy is set for these links, make sure JDownloader uses it too (Settings > Connection Manager)");
+	}
+
+	dl_links.insert(0, "-add-link".to_string());
+	
+	let jd = get_jd_path();
+
+	// JDownloader keeps running after we exit, so the child isn't waited on
+	#[allow(clippy::zombie_processes)]
+	let _ = process::Command::new(jd)
+		.args(dl_links)
+		.spawn()
+		.unwrap();
+
+	if let Err(e) = record_install(update, cfg, &mirror.link()) {
+		println!("Couldn't save the install manifest: {}", e);
+	}
+
+
+
+
+}
+
+// Remember what was installed in the config, and in the game folder for the next config, see detect
+fn record_install(update: &Update, cfg: &mut AppConfig, link: &str) -> std::io::Result<()> {
+	cfg.last_update_title = update.to().title();
+	cfg.last_update = update.to().last_update();
+	cfg.last_update_str = epoch_to_date(update.to().last_update());
+	cfg.build_id = update.to().build_id();
+	cfg.variant = update.to().variant();
+
+	let manifest = InstallManifest {
+		app_id: cfg.app_id(),
+		title: update.to().title(),
+		build_id: update.to().build_id(),
+		variant: update.to().variant().unwrap_or_default(),
+		released: update.to().last_update().parse::<i64>().unwrap_or(0),
+		installed: chrono::Utc::now().timestamp(),
+		link: link.to_string()
+	};
+	manifest.to_dir(&cfg.dir())
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::backend::HttpBackend;
+	use crate::mock_server::{MockServer, MockResponse};
+	use crate::session::HttpSession;
+	use crate::settings::HostSettings;
+
+	// A paste with only "Game | 01.02.2022\nThe links are in the next post.", encrypted like in the privatebin tests
+	const EMPTY_PASTE: &str = r#"{"v": 2, "adata": [["AAECAwQFBgcICQoLDA0ODw==", "cmluc2FsdCE=", 1000, 256, 128, "aes", "gcm", "none"], "plaintext", 0, 0], "ct": "RBrqSmJ97WuAdkLCgagp10b0mxfmm3tO+Lm9Y9aOOQ2r7l13S1G8YWLX1DeLu1h2ok83m4KcFCu6CQRlBDb/UmqVr2aRc1JArqjYe88oVg==", "meta": {"expire": "1week"}}"#;
+	const KEY_BASE58: &str = "4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw";
+
+	fn config(title: &str, date: &str, variant: Option<&[&str]>) -> AppConfig {
+		let mut cfg = AppConfig::new("292030".to_string(), String::new(), String::new(), title.to_string(), date.to_string(), None, None);
+		cfg.variant = variant.map(|v| v.iter().map(|t| t.to_string()).collect());
+		cfg
+	}
+
+	fn topic(texts: &[&str]) -> SearchResult {
+		let links = texts.iter().enumerate().map(|(i, text)| LinkText::new(&format!("https://mega.nz/file/{}", i), text)).collect();
+		SearchResult::new(LinkText::new("https://cs.rin.ru/forum/viewtopic.php?t=1", "Game"), String::new(), None, Vec::new(), links)
+	}
+
+	fn date(text: &str) -> String {
+		release::parse_date(text).unwrap().to_string()
+	}
+
+	#[test]
+	fn offers_updates_of_the_same_variant() {
+		let hosts = HostRegistry::defaults();
+		let res = topic(&["Game [Win64] | 01.02.2022", "Game [Linux] | 05.02.2022", "Game [Win64] | 03.02.2022 | +DLC", "Game [Win64] | 03.02.2022"]);
+
+		// Configs from before the releases were parsed have the tags in the title
+		let update = check_update(&res, &config("Game [Win64]", "01.02.2022", None), &hosts).unwrap();
+		assert_eq!((update.to().title(), update.to().last_update()), ("Game [Win64]".to_string(), date("03.02.2022")));
+		assert!(check_update(&res, &config("Game [Win64]", "03.02.2022", None), &hosts).is_none());
+		let update = check_update(&res, &config("Game [Linux]", "01.02.2022", None), &hosts).unwrap();
+		assert_eq!(update.mirrors()[0].link(), "https://mega.nz/file/1");
+		// Without the tags it's none of them
+		assert!(check_update(&res, &config("Game", "01.02.2022", None), &hosts).is_none());
+
+		// The variant from the last update picks between the releases of the same day
+		let update = check_update(&res, &config("Game [Win64]", "01.02.2022", Some(&["win64"])), &hosts).unwrap();
+		assert_eq!(update.mirrors()[0].link(), "https://mega.nz/file/3");
+		assert_eq!(update.to().variant(), Some(vec!["win64".to_string()]));
+		let update = check_update(&res, &config("Game [Win64]", "01.02.2022", Some(&["+DLC", "Win64"])), &hosts).unwrap();
+		assert_eq!(update.mirrors()[0].link(), "https://mega.nz/file/2");
+	}
+
+	#[test]
+	fn installed_update_is_detected() {
+		let dir = std::env::temp_dir().join(format!("rin_scraper_install_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let res = topic(&["Game [Win64] | 01.02.2022", "Game [Win64] | 03.02.2022 | Build 9755563"]);
+		let mut cfg = config("Game [Win64]", "01.02.2022", None);
+		// Configs were saved with \ separators
+		cfg.path = match cfg!(windows) {
+			true => dir.to_str().unwrap().to_string(),
+			false => dir.to_str().unwrap().replace('/', "\\")
+		};
+
+		let update = check_update(&res, &cfg, &HostRegistry::defaults()).unwrap();
+		record_install(&update, &mut cfg, &update.mirrors()[0].link()).unwrap();
+		assert_eq!(cfg.build_id, Some("9755563".to_string()));
+
+		let installed = crate::detect::detect(&dir).unwrap();
+		assert_eq!(installed.app_id, Some("292030".to_string()));
+		assert_eq!(installed.build_id, Some("9755563".to_string()));
+		assert_eq!(installed.title, Some("Game [Win64]".to_string()));
+		assert_eq!(installed.variant, Some(vec!["win64".to_string()]));
+		// The next check starts from it
+		let mut next = config(&installed.title.unwrap(), "03.02.2022", None);
+		next.variant = installed.variant;
+		next.build_id = installed.build_id;
+		assert!(check_update(&res, &next, &HostRegistry::defaults()).is_none());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn skips_empty_pastes() {
+		let server = MockServer::start().await;
+		server.route("GET", "/", vec![MockResponse::json(EMPTY_PASTE)]);
+		let mut backend = HttpBackend::new(HttpSession::new(&server.url(), server.requester()).unwrap());
+		let paste_host = HostSettings { kind: Some(LinkKind::Paste), resolver: Some(Resolver::PrivateBin), priority: Some(50), ..HostSettings::default() };
+		let hosts = HostRegistry::from_settings(&[("127.0.0.1".to_string(), paste_host)].into_iter().collect());
+
+		let mirrors = vec![
+			LinkText::new(&format!("{}/?0123456789abcdef#{}", server.url(), KEY_BASE58), "Game | 01.02.2022"),
+			LinkText::new("https://mega.nz/file/a#key", "Game | 01.02.2022")
+		];
+		let (mirror, links) = first_mirror(&mirrors, &mut backend, &hosts).await.unwrap();
+		assert_eq!(mirror.link(), "https://mega.nz/file/a#key");
+		assert_eq!(links, vec!["https://mega.nz/file/a#key"]);
+		assert_eq!(server.requests().len(), 1);
+
+		assert!(first_mirror(&mirrors[..1], &mut backend, &hosts).await.is_none());
+	}
+}
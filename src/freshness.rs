@@ -0,0 +1,220 @@
+use std::fmt;
+use serde_json::{json, Value};
+
+use crate::release::{self, Release};
+use crate::scrape::SearchResult;
+use crate::steam::SteamInfo;
+
+// How a release from the topic compares to a branch on Steam, the public one unless the config follows another
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Freshness {
+    UpToDate,
+    Behind(i64), // days
+    OlderBuild, // but not from an earlier day, or without a date
+    Newer, // than the public branch, probably a beta
+    Unknown
+}
+
+impl Freshness {
+    // Colour for the terminal
+    fn color(&self) -> &str {
+        match self {
+            Freshness::UpToDate => "\x1B[32m", // green
+            Freshness::Behind(_) | Freshness::OlderBuild => "\x1B[31m", // red
+            Freshness::Newer => "\x1B[33m", // yellow
+            Freshness::Unknown => ""
+        }
+    }
+
+    pub fn colored(&self) -> String {
+        match self {
+            Freshness::Unknown => self.to_string(),
+            _ => format!("{}{}\x1B[0m", self.color(), self)
+        }
+    }
+
+    // Name in the JSON report
+    fn key(&self) -> &str {
+        match self {
+            Freshness::UpToDate => "up_to_date",
+            Freshness::Behind(_) => "behind",
+            Freshness::OlderBuild => "older_build",
+            Freshness::Newer => "newer",
+            Freshness::Unknown => "unknown"
+        }
+    }
+}
+
+impl fmt::Display for Freshness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Freshness::UpToDate => write!(f, "up to date"),
+            Freshness::Behind(1) => write!(f, "1 day behind Steam"),
+            Freshness::Behind(days) => write!(f, "{} days behind Steam", days),
+            Freshness::OlderBuild => write!(f, "older build than Steam"),
+            Freshness::Newer => write!(f, "newer than the public branch (beta?)"),
+            Freshness::Unknown => write!(f, "unknown")
+        }
+    }
+}
+
+// Compare a release with the branch, or the public one if the app doesn't have it. Build ids are exact, so they win over dates
+pub fn freshness(release: &Release, steam: &SteamInfo, branch: &str) -> Freshness {
+    let public = match steam.branch(branch).or_else(|| steam.branch("public")) {
+        Some(public) => public,
+        None => return Freshness::Unknown
+    };
+    let updated = public.time_updated().parse::<i64>().unwrap_or(0);
+    let date = release.date;
+    // Whole days between the release and the update of the branch
+    let days_behind = |date: i64| (updated - date).div_euclid(86400);
+
+    if let (Some(build), Ok(public_build)) = (release.build_id, public.build_id().parse::<u64>()) {
+        return match build.cmp(&public_build) {
+            std::cmp::Ordering::Equal => Freshness::UpToDate,
+            std::cmp::Ordering::Greater => Freshness::Newer,
+            std::cmp::Ordering::Less => match date {
+                Some(date) if days_behind(date) > 0 => Freshness::Behind(days_behind(date)),
+                _ => Freshness::OlderBuild
+            }
+        };
+    }
+
+    match date {
+        Some(_) if updated == 0 => Freshness::Unknown,
+        Some(date) if days_behind(date) > 0 => Freshness::Behind(days_behind(date)),
+        Some(_) => Freshness::UpToDate,
+        None => Freshness::Unknown
+    }
+}
+
+// The Steam app a download link is compared with: the one at the same position if every release has its own app,
+// otherwise the first one (the game itself)
+pub fn steam_for(index: usize, steam_links: &[SteamInfo], dl_count: usize) -> Option<&SteamInfo> {
+    match steam_links.len() == dl_count {
+        true => steam_links.get(index),
+        false => steam_links.first()
+    }
+}
+
+// Freshness of every download link, in the same order
+pub fn report(res: &SearchResult, branch: &str) -> Vec<Freshness> {
+    let steam_links = res.steam_links();
+    let dl_links = res.dl_links();
+    dl_links.iter().enumerate()
+        .map(|(i, link)| match steam_for(i, &steam_links, dl_links.len()) {
+            Some(steam) => match release::from_result(res, link) {
+                Ok(release) => freshness(&release, steam, branch),
+                Err(_) => Freshness::Unknown
+            },
+            None => Freshness::Unknown
+        })
+        .collect()
+}
+
+// The report as JSON, for scripts
+pub fn report_json(res: &SearchResult, branch: &str) -> Value {
+    let steam_links = res.steam_links();
+    let dl_links = res.dl_links();
+
+    let releases: Vec<Value> = dl_links.iter().zip(report(res, branch)).enumerate()
+        .map(|(i, (link, status))| (i, link, release::from_result(res, link).ok(), status))
+        .map(|(i, link, release, status)| json!({
+            "text": link.text(),
+            "link": link.link(),
+            "title": release.as_ref().map(|r| r.title.clone()),
+            "date": release.as_ref().and_then(|r| r.date),
+            "build": release.as_ref().and_then(|r| r.build_id),
+            "version": release.as_ref().and_then(|r| r.version.clone()),
+            "platform": release.as_ref().and_then(|r| r.platform.clone()),
+            "tags": release.as_ref().map(|r| r.tags.clone()).unwrap_or_default(),
+            "formats": release.as_ref().map(|r| r.formats.iter().map(|f| f.key()).collect::<Vec<&str>>()).unwrap_or_default(),
+            "app_id": steam_for(i, &steam_links, dl_links.len()).map(|s| s.app_id()),
+            "status": status.key(),
+            "days_behind": match status { Freshness::Behind(days) => Some(days), _ => None }
+        }))
+        .collect();
+
+    let steam: Vec<Value> = steam_links.iter()
+        .map(|s| json!({
+            "app_id": s.app_id(),
+            "title": s.title(),
+            "build_id": s.build_id(),
+            "last_update": s.last_update().parse::<i64>().unwrap_or(0),
+            "branches": s.branches().iter().map(|b| json!({
+                "name": b.name(),
+                "build_id": b.build_id(),
+                "time_updated": b.time_updated().parse::<i64>().unwrap_or(0),
+                "pwd_required": b.pwd_required()
+            })).collect::<Vec<Value>>()
+        }))
+        .collect();
+
+    json!({
+        "topic": { "title": res.thread_info().text(), "url": res.thread_info().link(), "author": res.author() },
+        "branch": branch,
+        "steam": steam,
+        "releases": releases
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrape::LinkText;
+
+    const DAY: i64 = 86400;
+    const UPDATED: i64 = 1666083660;
+
+    fn steam() -> SteamInfo {
+        let json = json!({ "data": { "292030": {
+            "common": { "name": "The Witcher 3: Wild Hunt" },
+            "depots": { "branches": {
+                "public": { "buildid": "9755563", "timeupdated": UPDATED.to_string() },
+                "beta": { "buildid": "9800000", "timeupdated": (UPDATED + 3 * DAY).to_string() }
+            } }
+        } } });
+        SteamInfo::from_steamcmd("292030", &json).unwrap()
+    }
+
+    fn release(date: Option<i64>, build_id: Option<u64>) -> Release {
+        Release {
+            title: "The Witcher 3: Wild Hunt".to_string(),
+            heading: "The Witcher 3: Wild Hunt".to_string(),
+            date,
+            version: None,
+            build_id,
+            platform: None,
+            tags: Vec::new(),
+            formats: Vec::new(),
+            mirrors: vec![LinkText::new("https://example.com", "The Witcher 3: Wild Hunt")]
+        }
+    }
+
+    #[test]
+    fn compares_builds() {
+        let steam = steam();
+        assert_eq!(freshness(&release(None, Some(9755563)), &steam, "public"), Freshness::UpToDate);
+        assert_eq!(freshness(&release(None, Some(9800000)), &steam, "public"), Freshness::Newer);
+        assert_eq!(freshness(&release(Some(UPDATED - 10 * DAY), Some(9000000)), &steam, "public"), Freshness::Behind(10));
+        // An older build from the same day, or without a date, isn't "0 days behind"
+        assert_eq!(freshness(&release(None, Some(9000000)), &steam, "public"), Freshness::OlderBuild);
+        assert_eq!(freshness(&release(Some(UPDATED - 60), Some(9000000)), &steam, "public"), Freshness::OlderBuild);
+        assert_eq!(freshness(&release(Some(UPDATED - 60), Some(9755563)), &steam, "beta"), Freshness::Behind(3));
+    }
+
+    #[test]
+    fn compares_dates() {
+        let steam = steam();
+        assert_eq!(freshness(&release(Some(UPDATED - DAY), None), &steam, "public"), Freshness::Behind(1));
+        assert_eq!(freshness(&release(Some(UPDATED), None), &steam, "public"), Freshness::UpToDate);
+        assert_eq!(freshness(&release(Some(UPDATED), None), &steam, "beta"), Freshness::Behind(3));
+        assert_eq!(freshness(&release(None, None), &steam, "public"), Freshness::Unknown);
+
+        assert_eq!(Freshness::Behind(1).to_string(), "1 day behind Steam");
+        assert_eq!(Freshness::OlderBuild.to_string(), "older build than Steam");
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+use crate::scrape::LinkText;
+use crate::settings::HostSettings;
+
+// Sites that download links point to, what they are and if we can get the files from them.
+// The defaults can be changed and new hosts added in the hosts section of the settings
+
+
+// What a download link is, so it can be shown and downloaded the right way
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Paste, // a paste with the actual links, e.g. PrivateBin
+    File, // the file itself
+    Folder, // a shared folder with the files
+    Container, // a page that hides the links behind a captcha
+    Magnet,
+    Torrent // a .torrent file, e.g. an attachment of the post
+}
+
+impl LinkKind {
+    // Shown next to the link, files and pastes are what's expected so they don't get one
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            LinkKind::Paste | LinkKind::File => None,
+            LinkKind::Folder => Some("folder"),
+            LinkKind::Container => Some("container"),
+            LinkKind::Magnet => Some("magnet"),
+            LinkKind::Torrent => Some("torrent")
+        }
+    }
+
+    // JDownloader can't do torrents
+    pub fn is_torrent(&self) -> bool {
+        matches!(self, LinkKind::Magnet | LinkKind::Torrent)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Support {
+    Supported,
+    Unsupported, // shown, but the link is only printed when updating
+    Ignored // not a download link
+}
+
+// How the links to the files are taken out of a link
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolver {
+    Direct, // the link is given to the downloader as it is
+    PrivateBin
+}
+
+#[derive(Clone, Debug)]
+pub struct Host {
+    pub kind: LinkKind,
+    pub support: Support,
+    pub resolver: Resolver,
+    pub folders: Vec<String>, // parts of the link that make it a folder, e.g. "/folder/"
+    pub priority: i32 // which mirror of a release is tried first, the highest one
+}
+
+pub struct HostRegistry {
+    hosts: HashMap<String, Host> // domain, host
+}
+
+impl HostRegistry {
+    pub fn defaults() -> HostRegistry {
+        let mut hosts = HashMap::new();
+        let mut add = |domains: &[&str], kind, support, resolver, folders: &[&str], priority| {
+            for domain in domains {
+                let folders = folders.iter().map(|f| f.to_string()).collect();
+                hosts.insert(domain.to_string(), Host { kind, support, resolver, folders, priority });
+            }
+        };
+        // PrivateBin first as it usually has the links of every host, then the hosts JDownloader is best with
+        add(&["privatebin.rinuploads.org"], LinkKind::Paste, Support::Supported, Resolver::PrivateBin, &[], 50);
+        add(&["filecrypt.cc", "filecrypt.co"], LinkKind::Container, Support::Unsupported, Resolver::Direct, &[], 0);
+        add(&["drive.google.com"], LinkKind::File, Support::Supported, Resolver::Direct, &["/folders/"], 40);
+        add(&["mega.nz", "mega.co.nz"], LinkKind::File, Support::Supported, Resolver::Direct, &["/folder/", "#F!"], 40);
+        add(&["pixeldrain.com"], LinkKind::File, Support::Supported, Resolver::Direct, &["/l/"], 40);
+        add(&["gofile.io"], LinkKind::Folder, Support::Supported, Resolver::Direct, &[], 30);
+        add(&["1fichier.com"], LinkKind::File, Support::Supported, Resolver::Direct, &["/dir/"], 30);
+        add(&["mediafire.com"], LinkKind::File, Support::Supported, Resolver::Direct, &["/folder/", "mediafire.com/?"], 30);
+        add(&["dropbox.com"], LinkKind::File, Support::Supported, Resolver::Direct, &["/sh/", "/scl/fo/"], 20);
+        add(&["onedrive.live.com", "1drv.ms"], LinkKind::File, Support::Supported, Resolver::Direct, &["/f/"], 20);
+        HostRegistry { hosts }
+    }
+
+    // The defaults with the hosts from the settings, a new host is a supported file host unless it says otherwise
+    pub fn from_settings(settings: &HashMap<String, HostSettings>) -> HostRegistry {
+        let mut registry = HostRegistry::defaults();
+        for (domain, host) in settings {
+            let domain = domain.trim().to_lowercase().trim_start_matches("www.").to_string();
+            let entry = registry.hosts.entry(domain)
+                .or_insert(Host { kind: LinkKind::File, support: Support::Supported, resolver: Resolver::Direct, folders: Vec::new(), priority: 10 });
+            if let Some(kind) = host.kind { entry.kind = kind }
+            if let Some(support) = host.support { entry.support = support }
+            if let Some(resolver) = host.resolver { entry.resolver = resolver }
+            if let Some(folders) = &host.folders { entry.folders = folders.clone() }
+            if let Some(priority) = host.priority { entry.priority = priority }
+        }
+        registry
+    }
+
+    // Domain and host of a link, the most specific domain wins so a subdomain can be set up differently
+    pub fn lookup(&self, url: &str) -> Option<(String, Host)> {
+        let parsed = reqwest::Url::parse(url).ok()?;
+        let host = parsed.host_str()?.to_lowercase();
+        self.hosts.iter()
+            .filter(|(domain, _)| host == domain.as_str() || host.ends_with(&format!(".{}", domain)))
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(domain, h)| (domain.clone(), h.clone()))
+    }
+
+    // Mirrors are tried from the highest, the ones we can't download from come last
+    pub fn priority(&self, link: &LinkText) -> i32 {
+        if link.kind().map(|k| k.is_torrent()).unwrap_or(false) {
+            return -20;
+        }
+        match self.lookup(&link.link()) {
+            Some((_, host)) if host.support == Support::Supported => host.priority,
+            Some(_) => -10,
+            None => -30
+        }
+    }
+
+    // What the link is, None if it's not a download
+    pub fn classify(&self, url: &str, text: &str) -> Option<LinkKind> {
+        if url.to_lowercase().starts_with("magnet:") {
+            return Some(LinkKind::Magnet);
+        }
+        // Torrents can be on any host, attachments only have the file name in the text
+        let path = reqwest::Url::parse(url).map(|u| u.path().to_lowercase()).unwrap_or_default();
+        if path.ends_with(".torrent") || text.trim().to_lowercase().ends_with(".torrent") {
+            return Some(LinkKind::Torrent);
+        }
+
+        let (_, host) = self.lookup(url)?;
+        if host.support == Support::Ignored {
+            return None;
+        }
+        let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+        match host.kind == LinkKind::File && host.folders.iter().any(|f| rest.contains(f.as_str())) {
+            true => Some(LinkKind::Folder),
+            false => Some(host.kind)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(hosts: &[(&str, HostSettings)]) -> HashMap<String, HostSettings> {
+        hosts.iter().map(|(domain, host)| (domain.to_string(), host.clone())).collect()
+    }
+
+    #[test]
+    fn settings_change_the_hosts() {
+        let hosts = HostRegistry::from_settings(&settings(&[
+            // Only what's set changes
+            ("mega.nz", HostSettings { priority: Some(60), ..HostSettings::default() }),
+            ("filecrypt.cc", HostSettings { support: Some(Support::Ignored), ..HostSettings::default() }),
+            ("WWW.Example-Host.com ", HostSettings::default()),
+            ("paste.example.org", HostSettings { kind: Some(LinkKind::Paste), resolver: Some(Resolver::PrivateBin), ..HostSettings::default() }),
+            ("files.example.org", HostSettings { folders: Some(vec!["/share/".to_string()]), ..HostSettings::default() })
+        ]));
+
+        let (_, mega) = hosts.lookup("https://mega.nz/file/a").unwrap();
+        assert_eq!((mega.kind, mega.support, mega.priority, mega.folders.len()), (LinkKind::File, Support::Supported, 60, 2));
+        assert_eq!(hosts.classify("https://filecrypt.cc/Container/A.html", "Mirror"), None);
+
+        // New hosts are supported file hosts
+        let (domain, new) = hosts.lookup("https://example-host.com/f/abc").unwrap();
+        assert_eq!(domain, "example-host.com");
+        assert_eq!((new.kind, new.support, new.resolver, new.priority), (LinkKind::File, Support::Supported, Resolver::Direct, 10));
+        let (_, paste) = hosts.lookup("https://paste.example.org/?a#b").unwrap();
+        assert_eq!((paste.kind, paste.resolver), (LinkKind::Paste, Resolver::PrivateBin));
+        assert_eq!(hosts.classify("https://files.example.org/share/abc", "Files"), Some(LinkKind::Folder));
+        assert_eq!(hosts.classify("https://files.example.org/abc", "Files"), Some(LinkKind::File));
+    }
+
+    #[test]
+    fn subdomains_can_differ() {
+        let hosts = HostRegistry::from_settings(&settings(&[
+            ("example.org", HostSettings { priority: Some(5), ..HostSettings::default() }),
+            ("cdn.example.org", HostSettings { support: Some(Support::Unsupported), ..HostSettings::default() })
+        ]));
+        assert_eq!(hosts.lookup("https://example.org/a").unwrap().0, "example.org");
+        assert_eq!(hosts.lookup("https://dl.example.org/a").unwrap().0, "example.org");
+        // The most specific domain wins
+        assert_eq!(hosts.lookup("https://cdn.example.org/a").unwrap().0, "cdn.example.org");
+        assert_eq!(hosts.lookup("https://eu.cdn.example.org/a").unwrap().0, "cdn.example.org");
+        // Not a subdomain
+        assert!(hosts.lookup("https://notexample.org/a").is_none());
+        assert!(hosts.lookup("not a link").is_none());
+    }
+
+    #[test]
+    fn default_priority() {
+        let hosts = HostRegistry::from_settings(&settings(&[("example.org", HostSettings::default())]));
+        let priority = |url: &str| hosts.priority(&LinkText::new(url, "Game | 01.02.2022"));
+        assert_eq!(priority("https://privatebin.rinuploads.org/?a#b"), 50);
+        assert_eq!(priority("https://drive.google.com/file/d/a"), 40);
+        assert_eq!(priority("https://mega.nz/file/a"), 40);
+        assert_eq!(priority("https://gofile.io/d/a"), 30);
+        assert_eq!(priority("https://www.dropbox.com/s/a"), 20);
+        assert_eq!(priority("https://example.org/a"), 10);
+        // Then the ones we can't download from
+        assert_eq!(priority("https://filecrypt.cc/Container/A.html"), -10);
+        assert_eq!(hosts.priority(&LinkText::new("magnet:?xt=urn:btih:abc", "Game").with_kind(LinkKind::Magnet)), -20);
+        assert_eq!(priority("https://unknown.example.com/a"), -30);
+    }
+
+    #[test]
+    fn classifies_links() {
+        let hosts = HostRegistry::defaults();
+        let links = [
+            ("https://privatebin.rinuploads.org/?0123456789abcdef#4wBqpZM9", "Download", Some(LinkKind::Paste)),
+            ("https://filecrypt.cc/Container/0123456789.html", "Mirror", Some(LinkKind::Container)),
+            ("https://drive.google.com/file/d/1AbC/view", "Part 1", Some(LinkKind::File)),
+            ("https://drive.google.com/drive/folders/1AbC", "All parts", Some(LinkKind::Folder)),
+            ("https://mega.nz/file/AbC#key", "MEGA", Some(LinkKind::File)),
+            ("https://mega.nz/folder/AbC#key", "MEGA", Some(LinkKind::Folder)),
+            ("https://mega.co.nz/#F!AbC!key", "MEGA", Some(LinkKind::Folder)),
+            ("https://pixeldrain.com/u/AbC", "Pixeldrain", Some(LinkKind::File)),
+            ("https://pixeldrain.com/l/AbC", "Pixeldrain", Some(LinkKind::Folder)),
+            ("https://gofile.io/d/AbC", "Gofile", Some(LinkKind::Folder)),
+            ("https://1fichier.com/?abc123", "1fichier", Some(LinkKind::File)),
+            ("https://1fichier.com/dir/AbC", "1fichier", Some(LinkKind::Folder)),
+            ("https://www.mediafire.com/file/abc/Game.zip/file", "MediaFire", Some(LinkKind::File)),
+            // A fragment doesn't make it a folder
+            ("https://www.mediafire.com/file/abc/Game.zip/file#download", "MediaFire", Some(LinkKind::File)),
+            ("https://www.mediafire.com/folder/abc/Game", "MediaFire", Some(LinkKind::Folder)),
+            ("https://www.mediafire.com/?abc123", "MediaFire", Some(LinkKind::Folder)),
+            ("https://www.dropbox.com/s/abc/Game.zip?dl=0", "Dropbox", Some(LinkKind::File)),
+            ("https://www.dropbox.com/sh/abc/AAA", "Dropbox", Some(LinkKind::Folder)),
+            ("https://www.dropbox.com/scl/fo/abc/AAA", "Dropbox", Some(LinkKind::Folder)),
+            ("https://1drv.ms/u/s!AbC", "OneDrive", Some(LinkKind::File)),
+            ("https://1drv.ms/f/s!AbC", "OneDrive", Some(LinkKind::Folder)),
+            ("magnet:?xt=urn:btih:0123456789abcdef&dn=Game", "Magnet", Some(LinkKind::Magnet)),
+            ("MAGNET:?xt=urn:btih:0123456789abcdef", "Magnet", Some(LinkKind::Magnet)),
+            ("https://example.com/Game.torrent", "Torrent", Some(LinkKind::Torrent)),
+            // Attachments are only a torrent by their name
+            ("https://cs.rin.ru/forum/download/file.php?id=241877", "The.Witcher.3.Build.9755563.torrent", Some(LinkKind::Torrent)),
+            ("https://cs.rin.ru/forum/download/file.php?id=241878", "Crack.zip", None),
+            ("https://store.steampowered.com/app/292030/", "Steam", None),
+            ("https://example.com/signature", "My other uploads", None),
+            ("./viewtopic.php?p=1", "Older versions", None)
+        ];
+        for (url, text, kind) in links {
+            assert_eq!(hosts.classify(url, text), kind, "{}", url);
+        }
+    }
+}
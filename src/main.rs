@@ -1,58 +1,146 @@
+
 use std::path::Path;
-use thirtyfour::prelude::*;
-use dotenv;
-use home;
+use std::error::Error;
 
 
+// The original modules keep their style, so the lints it trips are only allowed in them
+#[allow(clippy::double_ended_iterator_last)]
 mod scrape;
-use scrape::{login, search, browser_init};
+use scrape::{login, search_topics, open_topic, restore_session, save_session};
+
+mod steam;
+mod backend;
+use backend::{ForumBackend, BackendResult, BackendError};
+mod parser;
+mod session;
+mod saved_session;
+use saved_session::SavedSession;
+mod privatebin;
 
+#[allow(clippy::clone_on_copy, clippy::needless_return, clippy::println_empty_string, clippy::single_component_path_imports)]
 mod display;
-use display::{show_info};
+use display::{show_info, select_topic, select_app};
 
+#[allow(clippy::println_empty_string)]
 mod downloader;
 use downloader::{check_update, ask_update, download_update};
 
+#[allow(clippy::single_component_path_imports, clippy::single_match)]
 mod config;
 use config::{AppConfig, create_config};
+
+mod cli;
+use cli::{Cli, Command, USAGE};
+
+mod query;
+mod request;
+use request::Requester;
+mod settings;
+use settings::{Settings, SteamSettings};
+mod cassette;
+use cassette::Cassette;
+mod steam_cache;
+use steam_cache::SteamCache;
+mod steam_provider;
+use steam_provider::SteamMetadata;
+mod applist;
+use applist::{AppList, Resolved};
+mod freshness;
+mod dlc;
+#[allow(dead_code)] // Only some of the Steam files are read so far
+mod vdf;
+mod detect;
+mod hosts;
+mod release;
+#[cfg(test)]
+mod mock_server;
+use hosts::HostRegistry;
+// How many pages of search results are fetched
+const SEARCH_PAGES: usize = 5;
+// And how many pages of a topic are crawled
+const TOPIC_PAGES: usize = 50;
+
+// Login with the username and password from the env and save the session for the next run
+async fn relogin(backend: &mut dyn ForumBackend, session_path: &Path) -> BackendResult<()> {
+    let name = std::env::var("RIN_NAME").expect("RIN_NAME is not set. You have to specify your username in .env file");
+    let pswd = std::env::var("RIN_PASS").expect("RIN_PASS is not set. You have to specify your username in .env file");
+
+    login(backend, &name, &pswd).await?;
+    save_session(backend, session_path).await
+}
+
+// Turn a game name into its app id, SCS topics always have the store link with it in the first post.
+// If the app list can't be loaded the name is searched for as it is
+async fn resolve_query(requests: &Requester, settings: &SteamSettings, query: &str) -> String {
+    if query.chars().all(|c| c.is_ascii_digit()) {
+        return query.to_string();
+    }
+    let list = match AppList::load(requests, settings).await {
+        Ok(list) => list,
+        Err(e) => {
+            println!("Couldn't load the Steam app list ({}), searching for '{}'", e, query);
+            return query.to_string();
+        }
+    };
+
+    match list.resolve(query) {
+        Resolved::App(app) => {
+            println!("Searching for {} ({})", app.name, app.appid);
+            app.appid.to_string()
+        }
+        Resolved::Candidates(apps) => match select_app(query, &apps) {
+            Some(app) => app.appid.to_string(),
+            None => query.to_string()
+        },
+        Resolved::NotFound => query.to_string()
+    }
+}
+
 // Clear the screen
 async fn clear() {
     print!("\x1B[2J\x1B[1;1H");
 }
 
 #[tokio::main]
-async fn main() -> WebDriverResult<()> {
+async fn main() -> Result<(), Box<dyn Error>> {
     // Clear the terminal
     clear().await;
     // std::env::set_var("RUST_BACKTRACE", "1");
-    // Get the cli arguments and make sure that there are 2 at minimum
-    let args: Vec<String> = std::env::args().into_iter().map(|x| x.to_string()).collect();
+    // Get the cli arguments
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli = match Cli::parse(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            println!("{}\n\n{}", e, USAGE);
+            return Ok(());
+        }
+    };
 
 
     // Check if config is available 
     let cfg_opt = AppConfig::from_file(Path::new("./app.dat"));
     let mut cfg = cfg_opt.clone().unwrap_or(AppConfig::empty());
 
-    let (query, cfg_loaded) = match cfg_opt {
-        Some(_) => {
-            (cfg.app_id(), true)
-        },
-        None => {
-            if args.len() < 2 {
-        
-                println!("Usage:\nrin-scraper {{query}}\nQuery is the keyword that you want to search with, SteamAppID is recommended");
-                return Ok(());
-            }
-            (args[1].clone(), false)
-        }
-    };
+    // Subcommands work with or without a config, the rest needs a query from one of them
+    let command = cli.command();
+    if command.is_none() && cfg_opt.is_none() && cli.query().is_none() {
+        println!("{}", USAGE);
+        return Ok(());
+    }
 
-    if query.to_lowercase() == "createconfig" {
-        create_config();
-        return Ok(())
+    match command {
+        Some(Command::CreateConfig) => {
+            create_config();
+            return Ok(())
+        }
+        // Only local files, nothing to set up for it
+        Some(Command::ClearCache) => {
+            println!("Removed {} cached apps", SteamCache::new(SteamCache::default_dir(), 0).clear()?);
+            return Ok(());
+        }
+        _ => {}
     }
     
-    
 
 
     // Create a temp path 
@@ -65,42 +153,115 @@ async fn main() -> WebDriverResult<()> {
     dotenv::from_path(home::home_dir().unwrap()).ok();
 
 
-    // Load the username and password
-    let name = std::env::var("RIN_NAME").expect("RIN_NAME is not set. You have to specify your username in .env file");
-    let pswd = std::env::var("RIN_PASS").expect("RIN_PASS is not set. You have to specify your username in .env file");
+    // Every request goes through one layer, with the limits from the settings
+    let settings = Settings::from_file(&Settings::default_path())?;
+    let mut requests = Requester::new(settings.requests, settings.proxy)?;
+    if let Some(dir) = cli.record() {
+        requests = requests.with_cassette(Cassette::record(&dir)?);
+    }
+    if let Some(dir) = cli.replay() {
+        requests = requests.with_cassette(Cassette::replay(&dir)?);
+    }
+    // Fail early if the proxy is down, instead of on the first request
+    if let Err(e) = requests.check_proxies().await {
+        println!("{}", e);
+        return Ok(());
+    }
+
+    // Replays have to show what was recorded, so they don't use the cache. Without it everything is fetched again
+    let steam_cache = match requests.is_replay() {
+        true => SteamCache::disabled(),
+        false => SteamCache::new(SteamCache::default_dir(), if cli.no_cache() { 0 } else { settings.steam.cache_ttl_secs })
+    };
 
+    // Which links are downloads and how to get them
+    let hosts = HostRegistry::from_settings(&settings.hosts);
 
-    let (driver,mut selenium) = browser_init().await?;
+    let steam = match steam_provider::from_settings(&settings.steam) {
+        Ok(providers) => SteamMetadata::new(providers, steam_cache),
+        Err(e) => {
+            println!("{}", e);
+            return Ok(());
+        }
+    };
+
+    if command == Some(Command::RefreshApps) {
+        let list = AppList::refresh(&requests, &settings.steam).await?;
+        println!("Downloaded the Steam app list, {} apps", list.len());
+        return Ok(());
+    }
+
+    // The config already has the app id
+    let (query, cfg_loaded) = match cfg_opt {
+        Some(_) => (cfg.app_id(), true),
+        None => (cli.query().unwrap_or_default(), false)
+    };
+    let query = match cfg_loaded || !cli.resolve() {
+        true => query,
+        false => resolve_query(&requests, &settings.steam, &query).await
+    };
+
+    // Start the browser, or a plain HTTP session if RIN_BACKEND=http
+    let mut backend = backend::from_env(requests.clone()).await?;
     
     
     
-    // Login
-    login(&driver, &name, &pswd).await?;
+    // Reuse the session from the last run, login only if there isn't one.
+    // Replayed pages were recorded logged in, so there's nothing to do for them
+    let session_path = SavedSession::default_path();
+    if !requests.is_replay() && !restore_session(backend.as_mut(), &session_path).await? {
+        relogin(backend.as_mut(), &session_path).await?;
+    }
 
 
-    // Search for query
-    let s_res = search(&driver, &query).await?;
+    // Search for query, the saved session may have expired since the last run
+    let search_query = cli.search_query(&query);
+    let hits = match search_topics(backend.as_mut(), &search_query, SEARCH_PAGES).await {
+        Err(BackendError::LoggedOut) => {
+            relogin(backend.as_mut(), &session_path).await?;
+            search_topics(backend.as_mut(), &search_query, SEARCH_PAGES).await?
+        }
+        hits => hits?
+    };
+    let hit = match hits.len() {
+        0 => {
+            println!("Couldn't find any topics for '{}'", search_query.keywords());
+            backend.quit().await?;
+            return Ok(());
+        }
+        1 => &hits[0],
+        // Let the user pick if there's more than one
+        _ => &hits[select_topic(&hits)]
+    };
+    let s_res = open_topic(backend.as_mut(), hit, TOPIC_PAGES, &steam, &hosts).await?;
 
 
+    // The names of DLCs come from the app list, they're matched by app id without it
+    let apps = match s_res.steam_links().iter().any(|s| !s.dlc().is_empty()) {
+        true => AppList::load(&requests, &settings.steam).await.ok(),
+        false => None
+    };
+
     // Show info from the search result
-    show_info(&s_res, tmp_dir).await?;
+    show_info(&s_res, tmp_dir, &requests, apps.as_ref(), &cfg.branch()).await?;
+    if let Some(path) = cli.report() {
+        std::fs::write(&path, serde_json::to_string_pretty(&freshness::report_json(&s_res, &cfg.branch()))?)?;
+    }
 
     // Check for updates
     if cfg_loaded {
-        let update = check_update(&s_res, &cfg);
+        let update = check_update(&s_res, &cfg, &hosts);
         if let Some(updt) = update {
             if ask_update(&updt) {
-                download_update(&s_res, &updt, &mut cfg, &driver).await;
+                download_update(&updt, &mut cfg, backend.as_mut(), &hosts).await;
                 cfg.to_file(Path::new("./app.dat")).expect("Error while saving config to file: ");
             }
         }
 
     }
 
-    // Quit the driver
-    driver.quit().await?;
-    // Kill the geckodriver thread
-    selenium.kill()?;
+    // Quit the driver and kill the geckodriver thread
+    backend.quit().await?;
 
 
 
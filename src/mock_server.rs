@@ -0,0 +1,143 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+
+use crate::request::Requester;
+use crate::settings::{RequestSettings, ProxySettings};
+
+// A local HTTP server for tests that answers with canned pages, like a phpBB forum would.
+// Every request is kept so tests can check what was sent
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String
+}
+
+impl MockResponse {
+    pub fn page(body: &str) -> MockResponse {
+        MockResponse { status: 200, headers: vec![("Content-Type".to_string(), "text/html; charset=utf-8".to_string())], body: body.to_string() }
+    }
+    pub fn json(body: &str) -> MockResponse {
+        MockResponse { status: 200, headers: vec![("Content-Type".to_string(), "application/json".to_string())], body: body.to_string() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String, // with the query
+    pub headers: HashMap<String, String>, // lowercase name, value
+    pub body: String
+}
+
+impl MockRequest {
+    // Value of a form field in the body
+    pub fn form_value(&self, name: &str) -> Option<String> {
+        reqwest::Url::parse(&format!("http://x/?{}", self.body)).ok()?
+            .query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.to_string())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    routes: HashMap<String, Vec<MockResponse>>, // "METHOD /path", responses in the order they're served
+    served: HashMap<String, usize>,
+    requests: Vec<MockRequest>
+}
+
+pub struct MockServer {
+    url: String,
+    state: Arc<Mutex<State>>
+}
+
+impl MockServer {
+    pub async fn start() -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let shared = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle(stream, shared.clone()));
+            }
+        });
+        MockServer { url, state }
+    }
+
+    pub fn url(&self) -> String { self.url.clone() }
+
+    // Answer requests to the path (without the query) with the responses in order, the last one is repeated
+    pub fn route(&self, method: &str, path: &str, responses: Vec<MockResponse>) {
+        self.state.lock().unwrap().routes.insert(format!("{} {}", method, path), responses);
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    // Request layer without delays or retries
+    pub fn requester(&self) -> Requester {
+        let settings = RequestSettings { min_delay_ms: 0, retries: 0, ..RequestSettings::default() };
+        Requester::new(settings, ProxySettings::default()).unwrap()
+    }
+}
+
+async fn handle(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+
+    // Headers first, then as much body as Content-Length says
+    let head_end = loop {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => data.extend_from_slice(&buf[..n])
+        }
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+    let mut lines = head.lines();
+    let mut start = lines.next().unwrap_or("").split_whitespace();
+    let (method, path) = (start.next().unwrap_or("").to_string(), start.next().unwrap_or("").to_string());
+    let headers: HashMap<String, String> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .collect();
+
+    let length = headers.get("content-length").and_then(|l| l.parse::<usize>().ok()).unwrap_or(0);
+    while data.len() < head_end + length {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => data.extend_from_slice(&buf[..n])
+        }
+    }
+    let body = String::from_utf8_lossy(&data[head_end..]).to_string();
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        let key = format!("{} {}", method, path.split('?').next().unwrap_or(""));
+        let count = *state.served.get(&key).unwrap_or(&0);
+        state.served.insert(key.clone(), count + 1);
+        state.requests.push(MockRequest { method, path, headers, body });
+
+        match state.routes.get(&key) {
+            Some(responses) => responses.get(count).or_else(|| responses.last()).cloned(),
+            None => None
+        }
+    };
+    let response = response.unwrap_or(MockResponse { status: 404, headers: Vec::new(), body: "Not found".to_string() });
+
+    let mut out = format!("HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n", response.status, response.body.len());
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    out.push_str("\r\n");
+    out.push_str(&response.body);
+    let _ = stream.write_all(out.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
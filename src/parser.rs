@@ -0,0 +1,422 @@
+use regex::Regex;
+use scraper::{Html, Selector, ElementRef, Node};
+use reqwest::Url;
+
+use crate::scrape::LinkText;
+
+// Parsing of phpBB pages by class names, ids and post anchors instead of absolute paths,
+// works with subsilver2 (which SCS uses) and prosilver markup
+
+
+// A topic from the search results or a forum
+#[derive(Debug, Clone)]
+pub struct TopicHit {
+    topic_id: String,
+    title: String,
+    href: String,
+    author: String,
+    replies: u32,
+    views: u32,
+    last_post: String
+}
+
+impl TopicHit {
+    pub fn topic_id(&self) -> String { self.topic_id.clone() }
+    pub fn title(&self) -> String { self.title.clone() }
+    pub fn href(&self) -> String { self.href.clone() }
+    pub fn author(&self) -> String { self.author.clone() }
+    pub fn replies(&self) -> u32 { self.replies }
+    pub fn views(&self) -> u32 { self.views }
+    pub fn last_post(&self) -> String { self.last_post.clone() }
+
+    pub fn with_href(self, href: &str) -> TopicHit {
+        TopicHit { href: href.to_string(), ..self }
+    }
+}
+
+// A single post of a topic
+#[derive(Debug, Clone)]
+pub struct Post {
+    post_id: String,
+    author: String,
+    posted: String,
+    text: String,
+    links: Vec<LinkText>,
+    images: Vec<String>
+}
+
+impl Post {
+    pub fn post_id(&self) -> String { self.post_id.clone() }
+    pub fn author(&self) -> String { self.author.clone() }
+    pub fn posted(&self) -> String { self.posted.clone() }
+    pub fn text(&self) -> String { self.text.clone() }
+    pub fn links(&self) -> Vec<LinkText> { self.links.clone() }
+    pub fn images(&self) -> Vec<String> { self.images.clone() }
+
+    // The post with relative links made absolute
+    pub fn with_base(self, base: &str) -> Post {
+        let base = match Url::parse(base) {
+            Ok(base) => base,
+            Err(_) => return self
+        };
+        let links = self.links.into_iter()
+            .map(|l| match Url::parse(&l.link()) {
+                Ok(_) => l,
+                Err(_) => match base.join(&l.link()) {
+                    Ok(url) => LinkText::new(url.as_str(), &l.text()),
+                    Err(_) => l
+                }
+            })
+            .collect();
+        Post { links, ..self }
+    }
+}
+
+
+fn sel(selector: &str) -> Selector {
+    Selector::parse(selector).unwrap()
+}
+
+fn text(elem: &ElementRef) -> String {
+    elem.text().collect::<String>().split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Text of a post line by line, with an empty line between paragraphs
+fn post_text(elem: &ElementRef) -> String {
+    let mut raw = String::new();
+    for node in elem.descendants() {
+        match node.value() {
+            // Line breaks in the source don't show
+            Node::Text(t) => raw.push_str(&t.replace('\n', " ")),
+            Node::Element(e) if matches!(e.name(), "br" | "p" | "div" | "li") => raw.push('\n'),
+            _ => {}
+        }
+    }
+    let lines: Vec<String> = raw.split('\n').map(|l| l.split_whitespace().collect::<Vec<&str>>().join(" ")).collect();
+    lines.split(|l| l.is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join("\n"))
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+fn has_class(elem: &ElementRef, class: &str) -> bool {
+    elem.value().classes().any(|c| c == class)
+}
+
+// Value of a parameter in a link, e.g. t=1234 in viewtopic.php?f=22&t=1234
+pub fn url_param(href: &str, name: &str) -> Option<String> {
+    let re = Regex::new(&format!(r"[?&;]{}=([^&#]+)", regex::escape(name))).unwrap();
+    re.captures(href).map(|cap| cap[1].to_string())
+}
+
+
+// Title of the topic on a viewtopic page
+pub fn topic_title(page: &str) -> Option<String> {
+    let doc = Html::parse_document(page);
+    let title = doc.select(&sel("h2 a, h2.topic-title a, h2")).next()?;
+    Some(text(&title)).filter(|t| !t.is_empty())
+}
+
+// Topics listed on a search results page
+pub fn topic_hits(page: &str) -> Vec<TopicHit> {
+    let doc = Html::parse_document(page);
+    let mut hits: Vec<TopicHit> = Vec::new();
+
+    for link in doc.select(&sel("a.topictitle")) {
+        let href = link.value().attr("href").unwrap_or("").replace("&amp;", "&");
+        // Links to topics always have the topic id
+        let topic_id = match url_param(&href, "t") {
+            Some(t) => t,
+            None => continue
+        };
+
+        // The row is a <tr> in subsilver2 and a <li class="row"> in prosilver
+        let row = link.ancestors()
+            .filter_map(ElementRef::wrap)
+            .find(|e| e.value().name() == "tr" || (e.value().name() == "li" && has_class(e, "row")));
+
+        let (author, replies, views, last_post) = match row {
+            Some(row) => row_details(&row),
+            None => (String::new(), 0, 0, String::new())
+        };
+
+        hits.push(TopicHit { topic_id, title: text(&link), href, author, replies, views, last_post });
+    }
+
+    hits
+}
+
+// Author, replies, views and last post time of a topic row
+fn row_details(row: &ElementRef) -> (String, u32, u32, String) {
+    let number = |s: String| s.split_whitespace().next().unwrap_or("0").replace(',', "").parse::<u32>().unwrap_or(0);
+
+    let author = row.select(&sel(".topicauthor, dt a.username, dt a.username-coloured")).next()
+        .map(|a| text(&a))
+        .unwrap_or_default();
+
+    // subsilver2 has replies, views, last post time and last poster in .topicdetails
+    let details: Vec<String> = row.select(&sel(".topicdetails")).map(|d| text(&d)).collect();
+    if details.len() >= 3 {
+        return (author, number(details[0].clone()), number(details[1].clone()), details[2].clone());
+    }
+
+    // prosilver has them in <dd> elements
+    let dd = |class: &str| row.select(&sel(&format!("dd.{}", class))).next().map(|d| text(&d)).unwrap_or_default();
+    let last_post = dd("lastpost");
+    let last_post = last_post.rsplit('»').next().unwrap_or("").trim().to_string();
+
+    (author, number(dd("posts")), number(dd("views")), last_post)
+}
+
+// Start (start=) of the next page of results or posts, if there is one. Only links to the script are checked,
+// and to the topic if there is one, posts can link to pages of other topics
+pub fn next_page_start(page: &str, script: &str, current: u32, topic: Option<&str>) -> Option<u32> {
+    let doc = Html::parse_document(page);
+    doc.select(&sel("a[href]"))
+        .map(|a| a.value().attr("href").unwrap())
+        .filter(|href| href.contains(script))
+        .filter(|href| topic.is_none() || url_param(href, "t").as_deref() == topic)
+        .filter_map(|href| url_param(href, "start"))
+        .filter_map(|st| st.parse::<u32>().ok())
+        .filter(|st| *st > current)
+        .min()
+}
+
+// Posts on a viewtopic page in the order they appear
+pub fn posts(page: &str) -> Vec<Post> {
+    let doc = Html::parse_document(page);
+    let id_re = Regex::new(r"^p([0-9]+)$").unwrap();
+    let content_sel = sel(".content");
+
+    let mut posts: Vec<Post> = Vec::new();
+    let mut post_id = String::new();
+    let mut author: Option<String> = None;
+    let mut posted: Option<String> = None;
+
+    // Walk the page in order: the post anchor comes first, then the author, the time and the body
+    for elem in doc.root_element().descendants().filter_map(ElementRef::wrap) {
+        let anchor = elem.value().attr("id").or_else(|| elem.value().attr("name"));
+        if let Some(cap) = anchor.and_then(|a| id_re.captures(a)) {
+            post_id = cap[1].to_string();
+            author = None;
+            posted = None;
+            continue;
+        }
+
+        if posted.is_none() {
+            posted = post_time(&elem);
+        }
+
+        if author.is_none() && (has_class(&elem, "postauthor") || has_class(&elem, "username") || has_class(&elem, "username-coloured")) {
+            author = Some(text(&elem));
+            continue;
+        }
+
+        // Attachments come after the text, their links belong to the post too
+        if is_attachments(&elem) {
+            if let Some(post) = posts.last_mut().filter(|p| p.post_id == post_id) {
+                post.links.extend(links(&elem));
+                // Not the icons of the file types
+                post.images.extend(images(&elem).into_iter().filter(|src| src.contains("download/file.php")));
+            }
+            continue;
+        }
+
+        // prosilver wraps the whole post in .postbody and keeps the text in .content
+        let is_body = (has_class(&elem, "postbody") && elem.select(&content_sel).next().is_none())
+            || (has_class(&elem, "content") && elem.ancestors().filter_map(ElementRef::wrap).any(|a| has_class(&a, "postbody")));
+        if !is_body {
+            continue;
+        }
+        // Signatures are also .postbody in subsilver2, only the first body belongs to the post
+        if posts.last().map(|p| p.post_id == post_id).unwrap_or(false) {
+            continue;
+        }
+
+        posts.push(Post {
+            post_id: post_id.clone(),
+            author: author.clone().unwrap_or_default(),
+            posted: posted.clone().unwrap_or_default(),
+            text: post_text(&elem),
+            links: links(&elem),
+            images: images(&elem)
+        });
+    }
+
+    posts
+}
+
+fn links(elem: &ElementRef) -> Vec<LinkText> {
+    elem.select(&sel("a[href]"))
+        .map(|a| LinkText::new(&a.value().attr("href").unwrap().replace("&amp;", "&"), &text(&a)))
+        .collect()
+}
+
+fn images(elem: &ElementRef) -> Vec<String> {
+    elem.select(&sel("img[src]"))
+        .map(|img| img.value().attr("src").unwrap().to_string())
+        .collect()
+}
+
+// The attachments of a post: dl.attachbox next to .content in prosilver, and in subsilver2
+// a table after .postbody that starts with an "Attachments:" row
+fn is_attachments(elem: &ElementRef) -> bool {
+    match elem.value().name() {
+        "dl" => has_class(elem, "attachbox"),
+        "table" => elem.select(&sel("td")).next()
+            .map(|td| has_class(&td, "row3") && text(&td).starts_with("Attachments"))
+            .unwrap_or(false),
+        _ => false
+    }
+}
+
+// Time of the post if the element holds it
+fn post_time(elem: &ElementRef) -> Option<String> {
+    let clean = |s: &str| s.replace('\u{a0}', " ").trim().to_string();
+
+    // Newer phpBB versions have a <time> element
+    if elem.value().name() == "time" {
+        return elem.value().attr("datetime").map(|t| t.to_string());
+    }
+
+    // subsilver2: <b>Posted:</b> Tue Oct 18, 2022 9:01 am
+    if (elem.value().name() == "b" || elem.value().name() == "strong") && text(elem).starts_with("Posted") {
+        let time: String = elem.next_siblings()
+            .map_while(|n| n.value().as_text().map(|t| t.to_string()))
+            .collect();
+        return Some(clean(&time)).filter(|t| !t.is_empty());
+    }
+
+    // prosilver: <p class="author">by Someone » Tue Oct 18, 2022 9:01 am</p>
+    if has_class(elem, "author") && text(elem).contains('»') {
+        return text(elem).rsplit('»').next().map(clean).filter(|t| !t.is_empty());
+    }
+
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEARCH: &str = include_str!("../tests/fixtures/search_results.html");
+    const TOPIC: &str = include_str!("../tests/fixtures/viewtopic.html");
+
+    #[test]
+    fn finds_topic_hits() {
+        let hits = topic_hits(SEARCH);
+        assert_eq!(hits.len(), 2);
+
+        assert_eq!(hits[0].topic_id(), "63410");
+        assert_eq!(hits[0].title(), "The Witcher 3: Wild Hunt [Win64] [Multi-Language]");
+        assert_eq!(hits[0].href(), "./viewtopic.php?f=22&t=63410&sid=fedcba9876543210fedcba9876543210");
+        assert_eq!(hits[0].author(), "Uploader");
+        assert_eq!((hits[0].replies(), hits[0].views()), (1856, 1204417));
+        assert_eq!(hits[0].last_post(), "Tue Oct 18, 2022 9:01 am");
+
+        assert_eq!(hits[1].topic_id(), "98127");
+        assert_eq!(hits[1].author(), "Newbie");
+        assert_eq!((hits[1].replies(), hits[1].views()), (0, 42));
+    }
+
+    #[test]
+    fn reads_row_details() {
+        let doc = Html::parse_document(SEARCH);
+        let rows: Vec<ElementRef> = doc.select(&sel("tr[valign=middle]")).collect();
+        assert_eq!(row_details(&rows[1]), ("Newbie".to_string(), 0, 42, "Sun Oct 16, 2022 11:47 pm".to_string()));
+
+        // prosilver rows
+        let prosilver = Html::parse_fragment(r#"<ul><li class="row"><dl>
+            <dt><a href="./viewtopic.php?t=1" class="topictitle">Game</a><br />by <a href="./memberlist.php?u=2" class="username">Someone</a></dt>
+            <dd class="posts">12 <dfn>Replies</dfn></dd><dd class="views">3,456 <dfn>Views</dfn></dd>
+            <dd class="lastpost"><span><dfn>Last post </dfn>by <a class="username">Other</a> » Tue Oct 18, 2022 9:01 am</span></dd>
+        </dl></li></ul>"#);
+        let row = prosilver.select(&sel("li.row")).next().unwrap();
+        assert_eq!(row_details(&row), ("Someone".to_string(), 12, 3456, "Tue Oct 18, 2022 9:01 am".to_string()));
+    }
+
+    #[test]
+    fn finds_next_page() {
+        assert_eq!(next_page_start(SEARCH, "search.php", 0, None), Some(25));
+        assert_eq!(next_page_start(SEARCH, "search.php", 25, None), Some(50));
+        assert_eq!(next_page_start(SEARCH, "search.php", 50, None), None);
+        // The pages of the topics in the results aren't pages of the search
+        assert_eq!(next_page_start(TOPIC, "viewtopic.php", 0, Some("63410")), Some(15));
+        assert_eq!(next_page_start(TOPIC, "viewtopic.php", 1845, Some("63410")), None);
+        assert_eq!(next_page_start(TOPIC, "viewtopic.php", 0, Some("1")), None);
+        assert_eq!(next_page_start(TOPIC, "search.php", 0, None), None);
+    }
+
+    #[test]
+    fn reads_posts() {
+        assert_eq!(topic_title(TOPIC).as_deref(), Some("The Witcher 3: Wild Hunt [Win64] [Multi-Language]"));
+
+        let posts = posts(TOPIC);
+        assert_eq!(posts.len(), 2);
+
+        let first = &posts[0];
+        assert_eq!(first.post_id(), "1489675");
+        assert_eq!(first.author(), "Uploader");
+        assert_eq!(first.posted(), "Mon Feb 01, 2016 4:21 pm");
+        // Lines of the post are kept, the build only goes with the links next to it
+        assert!(first.text().lines().any(|l| l == "The Witcher 3: Wild Hunt | 18.10.2022 | Build 9755563"), "{}", first.text());
+        // The avatar and the signature aren't part of the post
+        assert_eq!(first.images(), vec!["https://i.imgur.com/w3cover.jpg"]);
+        let links: Vec<(String, String)> = first.links().iter().map(|l| (l.link(), l.text())).collect();
+        assert_eq!(links, vec![
+            ("https://store.steampowered.com/app/292030/".to_string(), "https://store.steampowered.com/app/292030/".to_string()),
+            ("https://privatebin.net/?0123456789abcdef#4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw".to_string(), "Download".to_string()),
+            ("./viewtopic.php?p=2731001&sid=fedcba9876543210fedcba9876543210#p2731001".to_string(), "Older versions".to_string()),
+            // The attachments
+            ("./download/file.php?id=241877&sid=fedcba9876543210fedcba9876543210".to_string(), "The.Witcher.3.Wild.Hunt.Build.9755563.torrent".to_string()),
+            ("./download/file.php?id=241878&sid=fedcba9876543210fedcba9876543210".to_string(), "Crack.zip".to_string())
+        ]);
+
+        let first = first.clone().with_base("https://cs.rin.ru/forum/viewtopic.php?f=22&t=63410");
+        assert_eq!(first.links()[2].link(), "https://cs.rin.ru/forum/viewtopic.php?p=2731001&sid=fedcba9876543210fedcba9876543210#p2731001");
+
+        let reply = &posts[1];
+        assert_eq!((reply.post_id(), reply.author(), reply.posted()), ("1489702".to_string(), "Someone".to_string(), "Mon Feb 01, 2016 5:03 pm".to_string()));
+        assert_eq!(reply.text(), "Thanks! Works fine, the fix is here.");
+        assert!(reply.images().is_empty());
+        assert_eq!(reply.links()[0].link(), "https://mega.nz/file/fix#key");
+    }
+
+    #[test]
+    fn reads_prosilver_attachments() {
+        let page = r##"<div id="p42" class="post bg2"><div class="inner">
+            <div class="postbody">
+                <h3 class="first"><a href="#p42">Game</a></h3>
+                <p class="author">by <strong><a class="username">Uploader</a></strong> » Tue Oct 18, 2022 9:01 am</p>
+                <div class="content">Game | 18.10.2022 <a class="postlink" href="https://mega.nz/file/a">Mega</a></div>
+                <dl class="attachbox">
+                    <dt>Attachments</dt>
+                    <dd><dl class="file"><dt><img src="./images/upload_icons/torrent.gif" alt="" /> <a class="postlink" href="./download/file.php?id=7">Game.torrent</a></dt><dd>(35.2 KiB) Downloaded 12 times</dd></dl></dd>
+                    <dd><dl class="thumbnail"><dt><a href="./download/file.php?id=8&amp;mode=view"><img src="./download/file.php?id=8&amp;t=1" alt="cover.jpg" /></a></dt></dl></dd>
+                </dl>
+                <div id="sig42" class="signature"><a href="https://example.com/signature">My other uploads</a></div>
+            </div>
+            <dl class="postprofile" id="profile42"><dt><a class="username">Uploader</a></dt></dl>
+        </div></div>"##;
+        let posts = posts(page);
+        assert_eq!(posts.len(), 1);
+        let links: Vec<String> = posts[0].links().iter().map(|l| l.link()).collect();
+        assert_eq!(links, vec!["https://mega.nz/file/a", "./download/file.php?id=7", "./download/file.php?id=8&mode=view"]);
+        assert_eq!(posts[0].images(), vec!["./download/file.php?id=8&t=1"]);
+    }
+
+    #[test]
+    fn reads_post_times() {
+        let times = |page: &str| -> Vec<String> {
+            let doc = Html::parse_document(page);
+            let times = doc.root_element().descendants().filter_map(ElementRef::wrap).filter_map(|e| post_time(&e)).collect();
+            times
+        };
+        assert_eq!(times(TOPIC), vec!["Mon Feb 01, 2016 4:21 pm", "Mon Feb 01, 2016 5:03 pm"]);
+        assert_eq!(times(r##"<p class="author"><a href="#p1">Post</a>by <strong><a class="username">Someone</a></strong> » Tue Oct 18, 2022 9:01&nbsp;am</p>"##), vec!["Tue Oct 18, 2022 9:01 am"]);
+        assert_eq!(times(r#"<p class="author">by <time datetime="2022-10-18T09:01:00+00:00">Tue Oct 18, 2022 9:01 am</time></p>"#), vec!["2022-10-18T09:01:00+00:00"]);
+        assert!(times("<b>Joined:</b> Sat Jan 03, 2015 1:12 pm").is_empty());
+    }
+}
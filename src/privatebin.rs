@@ -0,0 +1,173 @@
+use std::io::Read;
+use aes_gcm::{AesGcm, aes::Aes256, aead::{Aead, KeyInit, Payload, consts::U16}};
+use flate2::read::DeflateDecoder;
+use hmac::Hmac;
+use regex::Regex;
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::session::SessionError;
+use crate::request::Requester;
+
+// Password used for pastes on privatebin.rinuploads.org
+pub const PASTE_PASSWORD: &str = "cs.rin.ru";
+
+
+// Get links from a PrivateBin paste without a browser, decrypting it the same way its javascript does
+pub async fn get_links(requests: &Requester, link: &str, password: &str) -> Result<Vec<String>, SessionError> {
+    let text = get_paste(requests, link, password).await?;
+
+    let re = Regex::new(r#"https?://[^\s"'<>]+"#).unwrap();
+    Ok(re.find_iter(&text).map(|m| m.as_str().to_string()).collect())
+}
+
+// Download and decrypt the text of a paste
+pub async fn get_paste(requests: &Requester, link: &str, password: &str) -> Result<String, SessionError> {
+    // The key is in the fragment, which never gets sent to the server
+    let (url, key) = link.split_once('#')
+        .ok_or_else(|| SessionError::Parse(format!("No key in paste link: {}", link)))?;
+    let key = bs58::decode(key).into_vec().map_err(|e| SessionError::Parse(e.to_string()))?;
+
+    let client = requests.client();
+    let request = client.get(url)
+        .header("X-Requested-With", "JSONHttpRequest")
+        .build()?;
+    let paste: Value = requests.send(&client, request).await?
+        .error_for_status()?
+        .json().await?;
+
+    decrypt(&paste, &key, password)
+}
+
+// Decrypt a version 2 paste
+fn decrypt(paste: &Value, key: &[u8], password: &str) -> Result<String, SessionError> {
+    let err = |msg: &str| SessionError::Parse(format!("PrivateBin: {}", msg));
+
+    if paste["v"].as_i64() != Some(2) {
+        return Err(err("only version 2 pastes are supported"));
+    }
+
+    // [iv, salt, iterations, key size, tag size, algorithm, mode, compression]
+    let adata = &paste["adata"];
+    let spec = &adata[0];
+    let iv = base64::decode(spec[0].as_str().unwrap_or("")).map_err(|_| err("invalid iv"))?;
+    let salt = base64::decode(spec[1].as_str().unwrap_or("")).map_err(|_| err("invalid salt"))?;
+    let iterations = spec[2].as_u64().ok_or_else(|| err("invalid iteration count"))? as u32;
+    let compression = spec[7].as_str().unwrap_or("none");
+
+    if spec[3].as_u64() != Some(256) || spec[4].as_u64() != Some(128) || spec[5] != "aes" || spec[6] != "gcm" {
+        return Err(err("unsupported cipher"));
+    }
+    if iv.len() != 16 {
+        return Err(err("unsupported iv length"));
+    }
+
+    // The password gets appended to the key from the url
+    let mut material = key.to_vec();
+    material.extend_from_slice(password.as_bytes());
+
+    let mut derived = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(&material, &salt, iterations, &mut derived);
+
+    let ct = base64::decode(paste["ct"].as_str().unwrap_or("")).map_err(|_| err("invalid cipher text"))?;
+    // The whole adata is authenticated, serialized like JSON.stringify does it
+    let aad = serde_json::to_string(adata).map_err(|e| err(&e.to_string()))?;
+
+    // PrivateBin uses a 128 bit iv instead of the usual 96 bits
+    let cipher = AesGcm::<Aes256, U16>::new_from_slice(&derived)
+        .map_err(|_| err("invalid key"))?;
+    let plain = cipher.decrypt(iv.as_slice().into(), Payload { msg: &ct, aad: aad.as_bytes() })
+        .map_err(|_| err("couldn't decrypt the paste, wrong password?"))?;
+
+    let plain = match compression {
+        "zlib" => {
+            let mut out = Vec::new();
+            DeflateDecoder::new(plain.as_slice()).read_to_end(&mut out).map_err(|e| err(&e.to_string()))?;
+            out
+        }
+        _ => plain
+    };
+
+    let data: Value = serde_json::from_slice(&plain).map_err(|e| err(&e.to_string()))?;
+    data["paste"].as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| err("paste has no text"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::Cassette;
+    use crate::mock_server::{MockServer, MockResponse};
+
+    // Pastes encrypted like PrivateBin does it (made with Python's cryptography, not this code),
+    // with the key 01..20 and the text below
+    const PASTE_ZLIB: &str = r#"{"v": 2, "adata": [["AAECAwQFBgcICQoLDA0ODw==", "cmluc2FsdCE=", 1000, 256, 128, "aes", "gcm", "zlib"], "plaintext", 0, 0], "ct": "evKjJZEpmEl6+XBpl1Te+MLdsOLNIhVxqGt6JpHzhvxF2WwFVP834SAUKp78UXwrbjbEJ71NanoUmjnxWGIDKvCWu0NI/7TkzYlawSWIXu9cNH5up4+2P5WuaH2L4YISliPflbE=", "meta": {"expire": "1week"}}"#;
+    const PASTE_NONE: &str = r#"{"v": 2, "adata": [["AAECAwQFBgcICQoLDA0ODw==", "cmluc2FsdCE=", 1000, 256, 128, "aes", "gcm", "none"], "plaintext", 0, 0], "ct": "RBrqSmJ97WuAdkLCgagp10b0mxfmm3tO+Lm9Y9aOBRG6vkIkChWrM2rT1DmFuhdlpk85loiJT22jChI+TjvjiHdCAyz+kjHzCbK8xQgIWKWJ21/2cjULwF0iUX80PJkj77KNV5+fB+N9IvUKqKZwxiY=", "meta": {"expire": "1week"}}"#;
+    const KEY_BASE58: &str = "4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw";
+    const TEXT: &str = "Game | 01.02.2022\nhttps://drive.google.com/file/d/abc\nhttps://mega.nz/file/def#key";
+
+    fn key() -> Vec<u8> {
+        (1..=32).collect()
+    }
+
+    #[test]
+    fn decrypts_known_pastes() {
+        for paste in [PASTE_ZLIB, PASTE_NONE] {
+            let paste: Value = serde_json::from_str(paste).unwrap();
+            assert_eq!(decrypt(&paste, &key(), PASTE_PASSWORD).unwrap(), TEXT);
+        }
+        assert_eq!(bs58::decode(KEY_BASE58).into_vec().unwrap(), key());
+    }
+
+    #[test]
+    fn rejects_wrong_pastes() {
+        let paste: Value = serde_json::from_str(PASTE_ZLIB).unwrap();
+        assert!(decrypt(&paste, &key(), "wrong").is_err());
+        assert!(decrypt(&paste, &key()[1..], PASTE_PASSWORD).is_err());
+
+        // The adata is authenticated too
+        let mut changed = paste.clone();
+        changed["adata"][1] = Value::from("markdown");
+        assert!(decrypt(&changed, &key(), PASTE_PASSWORD).is_err());
+
+        let mut v1 = paste;
+        v1["v"] = Value::from(1);
+        assert!(decrypt(&v1, &key(), PASTE_PASSWORD).is_err());
+    }
+
+    #[tokio::test]
+    async fn gets_links_from_the_paste() {
+        let server = MockServer::start().await;
+        server.route("GET", "/", vec![MockResponse::json(PASTE_ZLIB)]);
+
+        let link = format!("{}/?0123456789abcdef#{}", server.url(), KEY_BASE58);
+        let links = get_links(&server.requester(), &link, PASTE_PASSWORD).await.unwrap();
+        assert_eq!(links, vec!["https://drive.google.com/file/d/abc", "https://mega.nz/file/def#key"]);
+
+        // The key stays on our side, and the paste is asked for as JSON
+        let request = &server.requests()[0];
+        assert_eq!(request.path, "/?0123456789abcdef");
+        assert_eq!(request.headers["x-requested-with"], "JSONHttpRequest");
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_pastes() {
+        let dir = std::env::temp_dir().join(format!("rin_scraper_paste_{}", std::process::id()));
+        let server = MockServer::start().await;
+        server.route("GET", "/", vec![MockResponse::json(PASTE_NONE)]);
+        let link = format!("{}/?0123456789abcdef#{}", server.url(), KEY_BASE58);
+
+        let recording = server.requester().with_cassette(Cassette::record(&dir).unwrap());
+        assert!(recording.is_recording());
+        assert_eq!(get_paste(&recording, &link, PASTE_PASSWORD).await.unwrap(), TEXT);
+
+        // Nothing is sent when replaying
+        let replay = server.requester().with_cassette(Cassette::replay(&dir).unwrap());
+        assert_eq!(get_paste(&replay, &link, PASTE_PASSWORD).await.unwrap(), TEXT);
+        assert_eq!(server.requests().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
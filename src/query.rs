@@ -0,0 +1,203 @@
+use std::str::FromStr;
+
+// Search options of phpBB's search.php
+
+// Forum id of SCS (Steam Content Sharing)
+pub const SCS_FORUM: u32 = 22;
+
+
+// Match all or any of the keywords (terms=)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Terms { All, Any }
+
+// Where to look for the keywords (sf=)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchIn { Titles, FirstPost, FullText }
+
+// What to sort the results by (sk=)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey { Time, Author, Title, Forum, Subject }
+
+// Sort direction (sd=)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortDir { Asc, Desc }
+
+impl Terms {
+    fn param(&self) -> &str {
+        match self { Terms::All => "all", Terms::Any => "any" }
+    }
+}
+impl SearchIn {
+    fn param(&self) -> &str {
+        match self { SearchIn::Titles => "titleonly", SearchIn::FirstPost => "firstpost", SearchIn::FullText => "all" }
+    }
+}
+impl SortKey {
+    fn param(&self) -> &str {
+        match self { SortKey::Author => "a", SortKey::Time => "t", SortKey::Forum => "f", SortKey::Title => "i", SortKey::Subject => "s" }
+    }
+}
+impl SortDir {
+    fn param(&self) -> &str {
+        match self { SortDir::Asc => "a", SortDir::Desc => "d" }
+    }
+}
+
+impl FromStr for Terms {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Terms, String> {
+        match s.to_lowercase().as_str() {
+            "all" => Ok(Terms::All),
+            "any" => Ok(Terms::Any),
+            _ => Err(format!("Invalid terms: '{s}', expected all or any"))
+        }
+    }
+}
+impl FromStr for SearchIn {
+    type Err = String;
+    fn from_str(s: &str) -> Result<SearchIn, String> {
+        match s.to_lowercase().as_str() {
+            "titles" | "title" => Ok(SearchIn::Titles),
+            "firstpost" | "first" => Ok(SearchIn::FirstPost),
+            "fulltext" | "all" => Ok(SearchIn::FullText),
+            _ => Err(format!("Invalid search field: '{s}', expected titles, firstpost or fulltext"))
+        }
+    }
+}
+impl FromStr for SortKey {
+    type Err = String;
+    fn from_str(s: &str) -> Result<SortKey, String> {
+        match s.to_lowercase().as_str() {
+            "time" => Ok(SortKey::Time),
+            "author" => Ok(SortKey::Author),
+            "title" => Ok(SortKey::Title),
+            "forum" => Ok(SortKey::Forum),
+            "subject" => Ok(SortKey::Subject),
+            _ => Err(format!("Invalid sort key: '{s}', expected time, author, title, forum or subject"))
+        }
+    }
+}
+impl FromStr for SortDir {
+    type Err = String;
+    fn from_str(s: &str) -> Result<SortDir, String> {
+        match s.to_lowercase().as_str() {
+            "asc" | "a" => Ok(SortDir::Asc),
+            "desc" | "d" => Ok(SortDir::Desc),
+            _ => Err(format!("Invalid sort direction: '{s}', expected asc or desc"))
+        }
+    }
+}
+
+
+// A search on the forum, the defaults are the same as the old hardcoded search
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    keywords: String,
+    terms: Terms,
+    search_in: SearchIn,
+    sort_key: SortKey,
+    sort_dir: SortDir,
+    author: String,
+    days: u32,
+    forums: Vec<u32>,
+    chars: u32
+}
+
+impl SearchQuery {
+    pub fn new(keywords: &str) -> SearchQuery {
+        SearchQuery {
+            keywords: keywords.to_string(),
+            terms: Terms::Any,
+            search_in: SearchIn::FirstPost,
+            sort_key: SortKey::Time,
+            sort_dir: SortDir::Desc,
+            author: String::new(),
+            days: 0,
+            forums: vec![SCS_FORUM],
+            chars: 300
+        }
+    }
+
+    pub fn keywords(&self) -> String { self.keywords.clone() }
+
+    pub fn terms(mut self, terms: Terms) -> SearchQuery { self.terms = terms; self }
+    pub fn search_in(mut self, search_in: SearchIn) -> SearchQuery { self.search_in = search_in; self }
+    pub fn sort_key(mut self, sort_key: SortKey) -> SearchQuery { self.sort_key = sort_key; self }
+    pub fn sort_dir(mut self, sort_dir: SortDir) -> SearchQuery { self.sort_dir = sort_dir; self }
+    pub fn author(mut self, author: &str) -> SearchQuery { self.author = author.to_string(); self }
+    // Only posts from the last n days, 0 for all of them
+    pub fn days(mut self, days: u32) -> SearchQuery { self.days = days; self }
+    // Replaces the default SCS forum
+    pub fn forums(mut self, forums: Vec<u32>) -> SearchQuery { self.forums = forums; self }
+
+    // Search page for the results starting at start, relative to the forum url
+    pub fn path(&self, start: u32) -> String {
+        let mut params: Vec<(&str, String)> = vec![
+            ("keywords", self.keywords.clone()),
+            ("terms", self.terms.param().to_string()),
+            ("author", self.author.clone())
+        ];
+        for forum in &self.forums {
+            params.push(("fid[]", forum.to_string()));
+        }
+        params.extend([
+            ("sc", "1".to_string()),
+            ("sf", self.search_in.param().to_string()),
+            ("sk", self.sort_key.param().to_string()),
+            ("sd", self.sort_dir.param().to_string()),
+            ("sr", "topics".to_string()),
+            ("st", self.days.to_string()),
+            ("ch", self.chars.to_string()),
+            ("t", "0".to_string()),
+            ("start", start.to_string()),
+            ("submit", "Search".to_string())
+        ]);
+
+        let query = url::form_urlencoded::Serializer::new(String::new()).extend_pairs(params).finish();
+        format!("search.php?{}", query)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_search() {
+        assert_eq!(SearchQuery::new("witcher 3").path(0),
+            "search.php?keywords=witcher+3&terms=any&author=&fid%5B%5D=22&sc=1&sf=firstpost&sk=t&sd=d&sr=topics&st=0&ch=300&t=0&start=0&submit=Search");
+    }
+
+    #[test]
+    fn maps_the_options() {
+        let query = SearchQuery::new("Ü & 100%")
+            .terms(Terms::All)
+            .search_in(SearchIn::Titles)
+            .sort_key(SortKey::Subject)
+            .sort_dir(SortDir::Asc)
+            .author("Some One")
+            .days(7)
+            .forums(vec![10, 22]);
+        assert_eq!(query.path(25),
+            "search.php?keywords=%C3%9C+%26+100%25&terms=all&author=Some+One&fid%5B%5D=10&fid%5B%5D=22&sc=1&sf=titleonly&sk=s&sd=a&sr=topics&st=7&ch=300&t=0&start=25&submit=Search");
+
+        let param = |key: SortKey| SearchQuery::new("x").sort_key(key).path(0).split('&').find(|p| p.starts_with("sk=")).unwrap().to_string();
+        assert_eq!([SortKey::Time, SortKey::Author, SortKey::Title, SortKey::Forum].map(param), ["sk=t", "sk=a", "sk=i", "sk=f"]);
+        assert!(SearchQuery::new("x").search_in(SearchIn::FullText).path(0).contains("&sf=all&"));
+    }
+
+    #[test]
+    fn parses_option_values() {
+        assert_eq!("ANY".parse::<Terms>(), Ok(Terms::Any));
+        assert_eq!("first".parse::<SearchIn>(), Ok(SearchIn::FirstPost));
+        assert_eq!("fulltext".parse::<SearchIn>(), Ok(SearchIn::FullText));
+        assert_eq!("Forum".parse::<SortKey>(), Ok(SortKey::Forum));
+        assert_eq!("d".parse::<SortDir>(), Ok(SortDir::Desc));
+
+        assert!("some".parse::<Terms>().is_err());
+        assert!("posts".parse::<SearchIn>().is_err());
+        assert!("date".parse::<SortKey>().is_err());
+        assert_eq!("up".parse::<SortDir>(), Err("Invalid sort direction: 'up', expected asc or desc".to_string()));
+    }
+}
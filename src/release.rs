@@ -0,0 +1,437 @@
+use std::{cmp::Ordering, fmt};
+use chrono::NaiveDate;
+use regex::Regex;
+
+use crate::hosts::HostRegistry;
+use crate::scrape::{LinkText, SearchResult};
+
+// A release from the text of its link, e.g. "Game [Win64] | 01.02.2022 | Build 1234567 | Clean Steam Files".
+// The parts between | can be in any order, the first one is the title
+
+
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub title: String,
+    pub heading: String, // the first part as it's written, tags in brackets included
+    pub date: Option<i64>, // epoch
+    pub version: Option<String>,
+    pub build_id: Option<u64>,
+    pub platform: Option<String>,
+    pub tags: Vec<String>, // what kind of release it is, e.g. "Clean Steam Files" or "+DLC"
+    pub formats: Vec<Format>, // how the date, build and version were written
+    pub mirrors: Vec<LinkText>
+}
+
+#[derive(Debug)]
+pub enum ReleaseError {
+    NoTitle(String),
+    NoVersion(String) // no date, build or version, so it can't be compared with anything
+}
+
+impl fmt::Display for ReleaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReleaseError::NoTitle(text) => write!(f, "No title in '{}'", text),
+            ReleaseError::NoVersion(text) => write!(f, "No date, build or version in '{}'", text)
+        }
+    }
+}
+
+impl std::error::Error for ReleaseError {}
+
+impl Release {
+    // Platform and tags, which tell the variants of a game apart (e.g. Win64 and Linux, or +DLC)
+    pub fn variant(&self) -> Vec<String> {
+        normalize_tags(self.platform.iter().chain(self.tags.iter()))
+    }
+
+    // If the release is an update of the installed one. Configs have the first part of the link text as the title,
+    // so the tags in brackets have to be the same too. Newer configs also know the platform and tags of the rest
+    pub fn follows(&self, title: &str, variant: Option<&[String]>) -> bool {
+        let (title, tags) = split_title(title);
+        let (own_title, own_tags) = split_title(&self.heading);
+        own_title.to_lowercase() == title.to_lowercase()
+            && normalize_tags(own_tags.iter()) == normalize_tags(tags.iter())
+            && variant.map(|v| normalize_tags(v.iter()) == self.variant()).unwrap_or(true)
+    }
+
+    // The same release on another host. The build can come from the post, so it only counts when both have one
+    fn same(&self, other: &Release) -> bool {
+        self.title == other.title && self.date == other.date && self.version == other.version
+            && self.platform == other.platform && self.tags == other.tags
+            && (self.build_id == other.build_id || self.build_id.is_none() || other.build_id.is_none())
+    }
+}
+
+// How a date or version was written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    DayMonthYear, // 01.02.2022
+    YearMonthDay, // 2022-02-01
+    MonthName, // 1 February 2022, Feb 1, 2022
+    Build, // Build 1234567
+    Version // v1.2.3
+}
+
+impl Format {
+    pub fn key(&self) -> &str {
+        match self {
+            Format::DayMonthYear => "dd.mm.yyyy",
+            Format::YearMonthDay => "yyyy-mm-dd",
+            Format::MonthName => "month name",
+            Format::Build => "build",
+            Format::Version => "version"
+        }
+    }
+}
+
+// A date or version made comparable, only values of the same kind can be compared
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stamp {
+    Date(i64), // epoch
+    Build(u64),
+    Version(Vec<u32>) // the numbers, a letter at the end is one more (1.2a is 1.2.1)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recognized {
+    pub format: Format,
+    pub stamp: Stamp,
+    pub text: String // as it was written
+}
+
+const MONTHS: &str = r"jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?";
+
+fn month_number(name: &str) -> Option<u32> {
+    let months = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+    let short = name.to_lowercase().chars().take(3).collect::<String>();
+    months.iter().position(|m| *m == short).map(|i| i as u32 + 1)
+}
+
+fn epoch(year: &str, month: u32, day: &str) -> Option<Stamp> {
+    let date = NaiveDate::from_ymd_opt(year.parse().ok()?, month, day.parse().ok()?)?;
+    Some(Stamp::Date(date.and_hms(0, 0, 0).timestamp()))
+}
+
+fn version_parts(text: &str) -> Option<Stamp> {
+    let re = Regex::new(r"(?i)([0-9]+(?:\.[0-9]+)*)([a-z])?$").unwrap();
+    let cap = re.captures(text)?;
+    let mut parts = cap[1].split('.').map(|p| p.parse::<u32>().ok()).collect::<Option<Vec<u32>>>()?;
+    if let Some(letter) = cap.get(2) {
+        parts.push(letter.as_str().to_lowercase().chars().next()? as u32 - 'a' as u32 + 1);
+    }
+    Some(Stamp::Version(parts))
+}
+
+// Every date, build and version in the text, in the order they're written.
+// Dates are looked for first, so 01.02.2022 isn't also taken for a version
+pub fn recognize(text: &str) -> Vec<Recognized> {
+    let patterns: [(Format, String); 6] = [
+        (Format::YearMonthDay, r"\b([0-9]{4})-([0-9]{1,2})-([0-9]{1,2})\b".to_string()),
+        (Format::DayMonthYear, r"\b([0-9]{1,2})\.([0-9]{1,2})\.([0-9]{4})\b".to_string()),
+        (Format::MonthName, format!(r"(?i)\b([0-9]{{1,2}})(?:st|nd|rd|th)?\.?\s+(?:of\s+)?({MONTHS})\.?,?\s+([0-9]{{4}})\b")),
+        (Format::MonthName, format!(r"(?i)\b({MONTHS})\.?\s+([0-9]{{1,2}})(?:st|nd|rd|th)?,?\s+([0-9]{{4}})\b")),
+        (Format::Build, r"(?i)\bbuild\s*(?:id)?\s*[:#]?\s*([0-9]+)\b".to_string()),
+        (Format::Version, r"(?i)\b((?:v|ver\.?|version)\s*[0-9]+(?:\.[0-9]+)*[a-z]?|[0-9]+(?:\.[0-9]+)+[a-z]?)\b".to_string())
+    ];
+
+    // A date that isn't a real one still keeps its place, 31.02.2022 isn't a version either
+    let mut found: Vec<(usize, usize, Option<Recognized>)> = Vec::new();
+    for (format, pattern) in patterns.iter() {
+        let re = Regex::new(pattern).unwrap();
+        for cap in re.captures_iter(text) {
+            let whole = cap.get(0).unwrap();
+            if found.iter().any(|(start, end, _)| whole.start() < *end && *start < whole.end()) {
+                continue;
+            }
+            let stamp = match format {
+                Format::YearMonthDay => cap[2].parse().ok().and_then(|m| epoch(&cap[1], m, &cap[3])),
+                Format::DayMonthYear => cap[2].parse().ok().and_then(|m| epoch(&cap[3], m, &cap[1])),
+                // Day first or month first
+                Format::MonthName if cap[1].starts_with(|c: char| c.is_ascii_digit()) => month_number(&cap[2]).and_then(|m| epoch(&cap[3], m, &cap[1])),
+                Format::MonthName => month_number(&cap[1]).and_then(|m| epoch(&cap[3], m, &cap[2])),
+                Format::Build => cap[1].parse().ok().map(Stamp::Build),
+                Format::Version => version_parts(&cap[1])
+            };
+            let text = cap.get(1).filter(|_| *format == Format::Version).unwrap_or(whole).as_str().to_string();
+            found.push((whole.start(), whole.end(), stamp.map(|stamp| Recognized { format: *format, stamp, text })));
+        }
+    }
+    found.sort_by_key(|(start, _, _)| *start);
+    found.into_iter().filter_map(|(_, _, r)| r).collect()
+}
+
+// Date (epoch) in any of the formats
+pub fn parse_date(text: &str) -> Option<i64> {
+    recognize(text).into_iter().find_map(|r| match r.stamp {
+        Stamp::Date(date) => Some(date),
+        _ => None
+    })
+}
+
+// Build id, e.g. "Build 1234567"
+pub fn parse_build(text: &str) -> Option<u64> {
+    recognize(text).into_iter().find_map(|r| match r.stamp {
+        Stamp::Build(build) => Some(build),
+        _ => None
+    })
+}
+
+fn parse_platform(text: &str) -> Option<String> {
+    let re = Regex::new(r"(?i)^(win(?:dows)?\s*(?:32|64|x64|x86)?|linux(?:\s*(?:32|64))?|mac(?:os)?|osx|os x)$").unwrap();
+    re.find(text.trim()).map(|m| m.as_str().to_string())
+}
+
+// Tags in [] or () after the title, e.g. "Game [Win64] (Clean Steam Files)"
+fn normalize_tags<'a>(tags: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags.map(|t| t.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()).collect();
+    tags.sort();
+    tags
+}
+
+fn split_title(part: &str) -> (String, Vec<String>) {
+    let re = Regex::new(r"[\[(]([^\])]*)[\])]").unwrap();
+    let tags = re.captures_iter(part).map(|cap| cap[1].trim().to_string()).filter(|t| !t.is_empty()).collect();
+    let title = re.replace_all(part, "").split_whitespace().collect::<Vec<&str>>().join(" ");
+    (title, tags)
+}
+
+// Build of a link that doesn't have one in its text: on the line of the link, or on a line of the same
+// paragraph that isn't another release. A post can list releases of other platforms or older ones
+fn build_near(post_text: &str, link_text: &str) -> Option<u64> {
+    let paragraph = post_text.split("\n\n").find(|p| p.contains(link_text))?;
+    let line = paragraph.lines().find(|l| l.contains(link_text))?;
+    parse_build(line).or_else(|| paragraph.lines()
+        .filter(|l| !l.contains(link_text))
+        .filter(|l| !recognize(l).iter().any(|r| matches!(r.stamp, Stamp::Date(_) | Stamp::Version(_))))
+        .find_map(parse_build))
+}
+
+// Parse the release of a link, the build can also be next to it in the post
+pub fn parse(link: &LinkText, post_text: &str) -> Result<Release, ReleaseError> {
+    let text = link.text();
+    let mut parts = text.split('|').map(|p| p.trim());
+
+    let heading = parts.next().unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ");
+    let (title, mut tags) = split_title(&heading);
+    if title.is_empty() {
+        return Err(ReleaseError::NoTitle(text.clone()));
+    }
+
+    let mut release = Release {
+        title,
+        heading,
+        date: None,
+        version: None,
+        build_id: None,
+        platform: None,
+        tags: Vec::new(),
+        formats: Vec::new(),
+        mirrors: vec![link.clone()]
+    };
+
+    // Everything that isn't a date, build, platform or version is a tag
+    tags.extend(parts.filter(|p| !p.is_empty()).map(|p| p.to_string()));
+    for tag in tags {
+        let platform = parse_platform(&tag);
+        let found = recognize(&tag);
+        if platform.is_none() && found.is_empty() {
+            release.tags.push(tag);
+            continue;
+        }
+        release.platform = release.platform.or(platform);
+        for r in found {
+            // The first one of each kind counts
+            let first = match r.stamp {
+                Stamp::Date(date) if release.date.is_none() => { release.date = Some(date); true },
+                Stamp::Build(build) if release.build_id.is_none() => { release.build_id = Some(build); true },
+                Stamp::Version(_) if release.version.is_none() => { release.version = Some(r.text); true },
+                _ => false
+            };
+            if first {
+                release.formats.push(r.format);
+            }
+        }
+    }
+
+    if release.date.is_none() && release.build_id.is_none() && release.version.is_none() {
+        return Err(ReleaseError::NoVersion(text));
+    }
+    if release.build_id.is_none() {
+        release.build_id = build_near(post_text, &text);
+    }
+    Ok(release)
+}
+
+// Parse a download link of the topic with the text of the post it's in
+pub fn from_result(res: &SearchResult, link: &LinkText) -> Result<Release, ReleaseError> {
+    let post_text = res.posts().into_iter()
+        .find(|p| Some(p.post_id()) == link.source())
+        .map(|p| p.text())
+        .unwrap_or_default();
+    parse(link, &post_text)
+}
+
+// Releases of the topic in the order they were posted, with the links of every mirror in the order they should be tried
+pub fn releases(res: &SearchResult, hosts: &HostRegistry) -> Vec<Release> {
+    let mut releases: Vec<Release> = Vec::new();
+    for link in res.dl_links() {
+        let release = match from_result(res, &link) {
+            Ok(release) => release,
+            Err(_) => continue
+        };
+        match releases.iter_mut().find(|r| r.same(&release)) {
+            Some(known) => {
+                known.build_id = known.build_id.or(release.build_id);
+                known.mirrors.push(link);
+            },
+            None => releases.push(release)
+        }
+    }
+    // Mirrors on the same kind of host stay in the order they were posted
+    for release in releases.iter_mut() {
+        release.mirrors.sort_by_key(|m| -hosts.priority(m));
+    }
+    releases
+}
+
+// Which of two releases is newer: builds are exact, then the dates, then the versions. None if they can't be compared
+pub fn compare(a: &Release, b: &Release) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (a.build_id, b.build_id) {
+        return Some(a.cmp(&b));
+    }
+    if let (Some(a), Some(b)) = (a.date, b.date) {
+        return Some(a.cmp(&b));
+    }
+    let version = |r: &Release| r.version.as_deref().and_then(version_parts);
+    match (version(a), version(b)) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosts::LinkKind;
+
+    fn date(year: i32, month: u32, day: u32) -> Stamp {
+        Stamp::Date(NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms(0, 0, 0).timestamp())
+    }
+
+    fn link(text: &str) -> LinkText {
+        LinkText::new("https://drive.google.com/x", text)
+    }
+
+    // Link texts from SCS topics and what should be found in them
+    fn corpus() -> Vec<(&'static str, Vec<(Format, Stamp)>)> {
+        vec![
+            ("Cyberpunk 2077 | 01.02.2022", vec![(Format::DayMonthYear, date(2022, 2, 1))]),
+            ("Hades | 1.9.2021", vec![(Format::DayMonthYear, date(2021, 9, 1))]),
+            ("Elden Ring | 2022-03-25 | Build 8546120", vec![(Format::YearMonthDay, date(2022, 3, 25)), (Format::Build, Stamp::Build(8546120))]),
+            ("Terraria | Dec 22, 2021 | Build 7910390", vec![(Format::MonthName, date(2021, 12, 22)), (Format::Build, Stamp::Build(7910390))]),
+            ("Factorio | 1.1.53 | 17th January 2022", vec![(Format::Version, Stamp::Version(vec![1, 1, 53])), (Format::MonthName, date(2022, 1, 17))]),
+            ("Outer Wilds | Sept. 3rd, 2021", vec![(Format::MonthName, date(2021, 9, 3))]),
+            ("Disco Elysium | 5 of March 2021", vec![(Format::MonthName, date(2021, 3, 5))]),
+            ("Hollow Knight | v1.5.78.11833", vec![(Format::Version, Stamp::Version(vec![1, 5, 78, 11833]))]),
+            ("Risk of Rain 2 | Version 2", vec![(Format::Version, Stamp::Version(vec![2]))]),
+            ("Celeste | v1.02a", vec![(Format::Version, Stamp::Version(vec![1, 2, 1]))]),
+            ("Valheim [Win64] | 14.03.2022 | Build ID: 8371023 | Clean Steam Files", vec![(Format::DayMonthYear, date(2022, 3, 14)), (Format::Build, Stamp::Build(8371023))]),
+            ("Satisfactory | build #184260 | Update 5", vec![(Format::Build, Stamp::Build(184260))]),
+            ("Game | 2.5GB", vec![]),
+            ("Game | 31.02.2022", vec![]),
+            ("Game 2 + DLC", vec![])
+        ]
+    }
+
+    #[test]
+    fn recognizes_corpus() {
+        for (text, expected) in corpus() {
+            let found: Vec<(Format, Stamp)> = recognize(text).into_iter().map(|r| (r.format, r.stamp)).collect();
+            assert_eq!(found, expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn parses_release() {
+        let release = parse(&link("Valheim [Win64] | 14.03.2022 | v0.207.20 | Clean Steam Files | +DLC"), "Valheim [Win64] | 14.03.2022 | v0.207.20 | Clean Steam Files | +DLC\nBuild 8371023").unwrap();
+        assert_eq!(release.title, "Valheim");
+        assert_eq!(Some(Stamp::Date(release.date.unwrap())), Some(date(2022, 3, 14)));
+        assert_eq!(release.version.as_deref(), Some("v0.207.20"));
+        assert_eq!(release.build_id, Some(8371023));
+        assert_eq!(release.platform.as_deref(), Some("Win64"));
+        assert_eq!(release.tags, vec!["Clean Steam Files", "+DLC"]);
+        assert_eq!(release.formats, vec![Format::DayMonthYear, Format::Version]);
+
+        let release = parse(&link("Spiel Ü | 2022-02-06"), "").unwrap();
+        assert_eq!(release.title, "Spiel Ü");
+        assert_eq!(release.formats, vec![Format::YearMonthDay]);
+
+        assert!(matches!(parse(&link("| 01.02.2022"), ""), Err(ReleaseError::NoTitle(_))));
+        assert!(matches!(parse(&link("Game.torrent"), "Build 9"), Err(ReleaseError::NoVersion(_))));
+    }
+
+    #[test]
+    fn takes_the_build_next_to_the_link() {
+        let post = "Game [Win64] | 05.02.2022 | Build 9000\nGame [Linux] | 05.02.2022\n\nOlder:\nGame [Win64] | 01.02.2022 (Build 8000)\n\nGame [Mac] | 05.02.2022\nBuild 9001";
+        let build = |text| parse(&link(text), post).unwrap().build_id;
+        assert_eq!(build("Game [Win64] | 05.02.2022 | Build 9000"), Some(9000));
+        // Not the build of the release above it
+        assert_eq!(build("Game [Linux] | 05.02.2022"), None);
+        assert_eq!(build("Game [Win64] | 01.02.2022"), Some(8000));
+        assert_eq!(build("Game [Mac] | 05.02.2022"), Some(9001));
+        // Or of a link that isn't in the post
+        assert_eq!(build("Game [Win32] | 05.02.2022"), None);
+    }
+
+    #[test]
+    fn follows_the_installed_variant() {
+        let release = |text| parse(&link(text), "").unwrap();
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<String>>();
+
+        let win = release("Game [Win64] [Multi] | 01.02.2022 | Clean Steam Files");
+        assert_eq!((win.title.as_str(), win.heading.as_str()), ("Game", "Game [Win64] [Multi]"));
+        assert_eq!(win.variant(), tags(&["clean steam files", "multi", "win64"]));
+
+        // Configs have always had the first part of the link text as the title
+        assert!(win.follows("Game [Win64] [Multi]", None));
+        assert!(win.follows("game [Multi]  [WIN64]", None));
+        assert!(!win.follows("Game", None));
+        assert!(!win.follows("Game [Linux] [Multi]", None));
+        // Newer ones know the rest of the variant too
+        assert!(win.follows("Game [Win64] [Multi]", Some(&tags(&["Win64", "Multi", "Clean Steam Files"]))));
+        assert!(!win.follows("Game [Win64] [Multi]", Some(&tags(&["Win64", "Multi"]))));
+        assert!(release("Game | 01.02.2022").follows("Game", Some(&[])));
+    }
+
+    #[test]
+    fn groups_mirrors() {
+        let hosts = HostRegistry::defaults();
+        let links = vec![
+            LinkText::new("https://filecrypt.cc/Container/A.html", "Game | 01.02.2022"),
+            LinkText::new("magnet:?xt=urn:btih:abc", "Game | 01.02.2022").with_kind(LinkKind::Magnet),
+            LinkText::new("https://drive.google.com/file/d/a", "Game | 01.02.2022"),
+            LinkText::new("https://mega.nz/file/b", "Game | 02.02.2022"),
+            LinkText::new("https://privatebin.rinuploads.org/?a#b", "Game | 1 February 2022"),
+            LinkText::new("https://drive.google.com/file/d/c", "Game | 01.02.2022 | Clean Steam Files")
+        ];
+        let res = SearchResult::new(link("Game"), String::new(), None, Vec::new(), links);
+
+        let releases = releases(&res, &hosts);
+        let mirrors: Vec<Vec<String>> = releases.iter().map(|r| r.mirrors.iter().map(|m| m.link()).collect()).collect();
+        assert_eq!(mirrors, vec![
+            vec!["https://privatebin.rinuploads.org/?a#b", "https://drive.google.com/file/d/a", "https://filecrypt.cc/Container/A.html", "magnet:?xt=urn:btih:abc"],
+            vec!["https://mega.nz/file/b"],
+            vec!["https://drive.google.com/file/d/c"]
+        ]);
+    }
+
+    #[test]
+    fn compares_releases() {
+        let release = |text| parse(&link(text), "").unwrap();
+        assert_eq!(compare(&release("Game | 01.02.2022 | Build 10"), &release("Game | 05.02.2022 | Build 9")), Some(Ordering::Greater));
+        assert_eq!(compare(&release("Game | 1 Feb 2022"), &release("Game | 2022-02-01")), Some(Ordering::Equal));
+        assert_eq!(compare(&release("Game | v1.10"), &release("Game | v1.9.5")), Some(Ordering::Greater));
+        assert_eq!(compare(&release("Game | v1.10"), &release("Game | 01.02.2022")), None);
+    }
+}
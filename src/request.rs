@@ -0,0 +1,374 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::Duration};
+use rand::Rng;
+use reqwest::{Client, ClientBuilder, Proxy, Request, Response, ResponseBuilderExt, StatusCode, header::{CONTENT_TYPE, RETRY_AFTER}};
+use thirtyfour::{WebDriver, prelude::WebDriverResult};
+use tokio::time::Instant;
+
+use crate::settings::{RequestSettings, ProxySettings};
+use crate::cassette::{self, Cassette};
+use crate::session::SessionError;
+
+// Every request (and browser navigation) goes through here, it keeps a minimum delay between
+// requests to the same host and retries failed ones with exponential backoff.
+// With a cassette the responses are recorded, or replayed instead of sending anything
+
+
+#[derive(Clone)]
+pub struct Requester {
+    client: Client,
+    settings: RequestSettings,
+    proxy: ProxySettings,
+    next_turn: Arc<Mutex<HashMap<String, Instant>>>, // host, when the next request can be sent
+    cassette: Option<Arc<Cassette>>
+}
+
+impl Requester {
+    pub fn new(settings: RequestSettings, proxy: ProxySettings) -> Result<Requester, reqwest::Error> {
+        let client = client_builder(&proxy).build()?;
+        Ok(Requester { client, settings, proxy, next_turn: Arc::new(Mutex::new(HashMap::new())), cassette: None })
+    }
+
+    pub fn with_cassette(self, cassette: Cassette) -> Requester {
+        Requester { cassette: Some(Arc::new(cassette)), ..self }
+    }
+
+    // If responses come from a cassette instead of the network
+    pub fn is_replay(&self) -> bool {
+        self.cassette.as_ref().map(|c| c.is_replay()).unwrap_or(false)
+    }
+    pub fn is_recording(&self) -> bool {
+        self.cassette.as_ref().map(|c| c.is_recording()).unwrap_or(false)
+    }
+
+    // Client for requests that don't need the forum session
+    pub fn client(&self) -> Client { self.client.clone() }
+    pub fn proxy(&self) -> ProxySettings { self.proxy.clone() }
+
+    // Builder for other clients (like the forum session's), with the proxy set
+    pub fn client_builder(&self) -> ClientBuilder {
+        client_builder(&self.proxy)
+    }
+
+    // Make sure every proxy can be reached, so we fail early with a clear error instead of on the first request
+    pub async fn check_proxies(&self) -> Result<(), String> {
+        if self.is_replay() {
+            return Ok(());
+        }
+        for proxy in self.proxy.proxies() {
+            let url = reqwest::Url::parse(&proxy).map_err(|e| format!("Invalid proxy '{}': {}", proxy, e))?;
+            let host = url.host_str().ok_or_else(|| format!("Invalid proxy '{}': no host", proxy))?;
+            let port = url.port_or_known_default().unwrap_or(1080);
+
+            match tokio::time::timeout(Duration::from_secs(10), tokio::net::TcpStream::connect((host, port))).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => return Err(format!("Couldn't reach the proxy {}: {}", proxy, e)),
+                Err(_) => return Err(format!("Couldn't reach the proxy {}: timed out", proxy))
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Response, SessionError> {
+        self.send(&self.client, self.client.get(url).build()?).await
+    }
+
+    // Send the request with the client, retrying on connection errors, timeouts and server errors.
+    // The last response is returned as it is, so check its status
+    pub async fn send(&self, client: &Client, request: Request) -> Result<Response, SessionError> {
+        let url = request.url().to_string();
+        let key = cassette::key(request.method().as_str(), &url);
+        if self.is_replay() {
+            return self.replay(&key, &url);
+        }
+
+        let mut attempt = 0;
+        loop {
+            // Requests with a streamed body can't be sent again
+            let (req, retry) = match request.try_clone() {
+                Some(req) => (req, attempt < self.settings.retries),
+                None => return Ok(self.record(&key, self.execute(client, request).await?).await?)
+            };
+
+            let wait = match self.execute(client, req).await {
+                Ok(res) if retry && is_transient(res.status()) => {
+                    self.log(&format!("{} from {}", res.status(), url));
+                    // A server can ask for hours, we don't wait longer than for any other retry
+                    retry_after(&res)
+                        .map(|wait| wait.min(Duration::from_millis(self.settings.max_backoff_ms)))
+                        .unwrap_or_else(|| self.backoff(attempt))
+                }
+                Err(e) if retry && (e.is_connect() || e.is_timeout()) => {
+                    self.log(&format!("{} from {}", e, url));
+                    self.backoff(attempt)
+                }
+                res => return Ok(self.record(&key, res?).await?)
+            };
+
+            self.log(&format!("Retrying in {:.1}s", wait.as_secs_f32()));
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    async fn execute(&self, client: &Client, req: Request) -> Result<Response, reqwest::Error> {
+        self.wait_turn(req.url().as_str()).await;
+        self.log(&format!("{} {}", req.method(), req.url()));
+        client.execute(req).await
+    }
+
+    // Save the response to the cassette, the body has to be read for that so the response is rebuilt
+    async fn record(&self, key: &str, res: Response) -> Result<Response, reqwest::Error> {
+        let cassette = match &self.cassette {
+            Some(cassette) => cassette,
+            None => return Ok(res)
+        };
+
+        let status = res.status();
+        let url = res.url().clone();
+        let headers = res.headers().clone();
+        let body = res.bytes().await?;
+
+        let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+        if let Err(e) = cassette.save(key, url.as_str(), status.as_u16(), content_type, &body) {
+            eprintln!("[record] Couldn't save {}: {}", key, e);
+        }
+
+        let mut builder = http::Response::builder().status(status).url(url);
+        for (name, value) in headers.iter() {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(body).unwrap().into())
+    }
+
+    // The recorded response, or a 404 if there isn't one. Fails if the entry was edited into something invalid
+    fn replay(&self, key: &str, url: &str) -> Result<Response, SessionError> {
+        let (status, url, content_type, body) = match self.cassette.as_ref().and_then(|c| c.load(key)) {
+            Some((entry, body)) => (entry.status, entry.url, entry.content_type, body),
+            None => {
+                eprintln!("[replay] No recorded response for {}", key);
+                (404, url.to_string(), None, Vec::new())
+            }
+        };
+        self.log(&format!("REPLAY {}", key));
+
+        let err = |e: &dyn std::fmt::Display| SessionError::Replay(format!("{}: {}", key, e));
+        let mut builder = http::Response::builder()
+            .status(status)
+            .url(reqwest::Url::parse(&url).map_err(|e| err(&e))?);
+        if let Some(content_type) = content_type {
+            builder = builder.header(CONTENT_TYPE, content_type);
+        }
+        builder.body(body).map(Response::from).map_err(|e| err(&e))
+    }
+
+    // Save the source of a page open in the browser
+    pub fn record_page(&self, url: &str, final_url: &str, source: &str) {
+        if let Some(cassette) = &self.cassette {
+            if let Err(e) = cassette.save_page(url, final_url, source) {
+                eprintln!("[record] Couldn't save {}: {}", url, e);
+            }
+        }
+    }
+
+    // Navigate the browser to the url, with the same delays and retries as send()
+    pub async fn goto(&self, driver: &WebDriver, url: &str) -> WebDriverResult<()> {
+        let mut attempt = 0;
+        loop {
+            self.wait_turn(url).await;
+            self.log(&format!("GOTO {}", url));
+
+            match driver.goto(url).await {
+                Err(e) if attempt < self.settings.retries => {
+                    let wait = self.backoff(attempt);
+                    self.log(&format!("{} from {}, retrying in {:.1}s", e, url, wait.as_secs_f32()));
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                res => return res
+            }
+        }
+    }
+
+    // Wait until enough time has passed since the last request to the host
+    pub async fn wait_turn(&self, url: &str) {
+        let host = reqwest::Url::parse(url).ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_default();
+        let delay = Duration::from_millis(*self.settings.host_delay_ms.get(&host).unwrap_or(&self.settings.min_delay_ms));
+
+        // Take the next free turn, so requests sent at the same time are spread out too
+        let turn = {
+            let mut next_turn = self.next_turn.lock().unwrap();
+            let turn = next_turn.get(&host).copied().unwrap_or_else(Instant::now).max(Instant::now());
+            next_turn.insert(host, turn + delay);
+            turn
+        };
+        tokio::time::sleep_until(turn).await;
+    }
+
+    // Exponential backoff with jitter, so retries from different requests don't line up
+    fn backoff(&self, attempt: u32) -> Duration {
+        let base = self.settings.backoff_ms.saturating_mul(2u64.saturating_pow(attempt)).min(self.settings.max_backoff_ms);
+        let jitter = rand::thread_rng().gen_range(0..=base / 2);
+        Duration::from_millis(base / 2 + jitter)
+    }
+
+    fn log(&self, msg: &str) {
+        if self.settings.log {
+            eprintln!("[requests] {}", msg);
+        }
+    }
+}
+
+// Client builder that sends every request through the proxy of its host
+fn client_builder(proxy: &ProxySettings) -> ClientBuilder {
+    let builder = Client::builder();
+    if proxy.proxies().is_empty() {
+        return builder;
+    }
+
+    let proxy = proxy.clone();
+    builder.proxy(Proxy::custom(move |url| {
+        proxy.for_host(url.host_str()?).and_then(|p| reqwest::Url::parse(&p).ok())
+    }))
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+}
+
+// How long the server asked us to wait with Retry-After
+fn retry_after(res: &Response) -> Option<Duration> {
+    if res.status() != StatusCode::TOO_MANY_REQUESTS && res.status() != StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+    parse_retry_after(res.headers().get(RETRY_AFTER)?.to_str().ok()?, chrono::Utc::now().timestamp())
+}
+
+// Retry-After is either seconds or a date, now is the epoch it's counted from
+fn parse_retry_after(value: &str, now: i64) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let secs = (date.timestamp() - now).max(0);
+    Some(Duration::from_secs(secs as u64))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::mock_server::{MockServer, MockResponse};
+
+    fn requester(settings: RequestSettings) -> Requester {
+        Requester::new(settings, ProxySettings::default()).unwrap()
+    }
+
+    fn unavailable(retry_after: &str) -> MockResponse {
+        MockResponse { status: 503, headers: vec![("Retry-After".to_string(), retry_after.to_string())], body: String::new() }
+    }
+
+    #[test]
+    fn backoff_stays_in_bounds() {
+        let requests = requester(RequestSettings { backoff_ms: 100, max_backoff_ms: 1000, ..RequestSettings::default() });
+        for attempt in 0..40 {
+            let base = (100 * 2u64.saturating_pow(attempt)).min(1000);
+            for _ in 0..20 {
+                let wait = requests.backoff(attempt).as_millis() as u64;
+                assert!(base / 2 <= wait && wait <= base, "attempt {}: {}ms", attempt, wait);
+            }
+        }
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        // Wed, 21 Oct 2015 07:28:00 GMT
+        let now = 1445412480;
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 ", now), Some(Duration::from_secs(0)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now), Some(Duration::from_secs(0)));
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(parse_retry_after("-5", now), None);
+    }
+
+    #[tokio::test]
+    async fn spaces_requests_per_host() {
+        let settings = RequestSettings { min_delay_ms: 100, host_delay_ms: [("slow.example".to_string(), 300)].into_iter().collect(), ..RequestSettings::default() };
+        let requests = requester(settings);
+
+        let start = Instant::now();
+        requests.wait_turn("https://a.example/1").await;
+        requests.wait_turn("https://b.example/1").await;
+        requests.wait_turn("https://slow.example/1").await;
+        // Other hosts don't wait for each other
+        assert!(start.elapsed() < Duration::from_millis(100), "{:?}", start.elapsed());
+        requests.wait_turn("https://a.example/2").await;
+        requests.wait_turn("https://a.example/3").await;
+        assert!(start.elapsed() >= Duration::from_millis(200), "{:?}", start.elapsed());
+        requests.wait_turn("https://slow.example/2").await;
+        assert!(start.elapsed() >= Duration::from_millis(300), "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let server = MockServer::start().await;
+        server.route("GET", "/flaky", vec![unavailable("0"), MockResponse { status: 502, headers: Vec::new(), body: String::new() }, MockResponse::page("ok")]);
+        server.route("GET", "/down", vec![MockResponse { status: 500, headers: Vec::new(), body: String::new() }]);
+        let requests = requester(RequestSettings { min_delay_ms: 0, retries: 2, backoff_ms: 10, max_backoff_ms: 20, ..RequestSettings::default() });
+
+        let res = requests.get(&format!("{}/flaky", server.url())).await.unwrap();
+        assert_eq!((res.status().as_u16(), res.text().await.unwrap()), (200, "ok".to_string()));
+        // The last response is returned when the retries run out
+        let res = requests.get(&format!("{}/down", server.url())).await.unwrap();
+        assert_eq!(res.status().as_u16(), 500);
+        let paths: Vec<String> = server.requests().iter().map(|r| r.path.clone()).collect();
+        assert_eq!(paths, vec!["/flaky", "/flaky", "/flaky", "/down", "/down", "/down"]);
+        // Not found isn't retried
+        assert_eq!(requests.get(&format!("{}/missing", server.url())).await.unwrap().status().as_u16(), 404);
+        assert_eq!(server.requests().len(), 7);
+    }
+
+    #[tokio::test]
+    async fn caps_retry_after() {
+        let server = MockServer::start().await;
+        server.route("GET", "/busy", vec![unavailable("3600"), MockResponse::page("ok")]);
+        let requests = requester(RequestSettings { min_delay_ms: 0, retries: 1, backoff_ms: 10, max_backoff_ms: 50, ..RequestSettings::default() });
+
+        let start = Instant::now();
+        let res = requests.get(&format!("{}/busy", server.url())).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert!(start.elapsed() < Duration::from_secs(5), "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn replays_the_cassette() {
+        let dir = std::env::temp_dir().join(format!("rin_scraper_replay_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let recording = Cassette::record(&dir).unwrap();
+        let page = "https://a.example/page?sid=0123";
+        recording.save(&cassette::key("GET", page), "https://a.example/page", 200, Some("text/html".to_string()), b"one").unwrap();
+        recording.save(&cassette::key("GET", page), "https://a.example/moved", 200, None, b"two").unwrap();
+        recording.save(&cassette::key("GET", "https://a.example/broken"), "not a url", 200, None, b"").unwrap();
+        recording.save(&cassette::key("GET", "https://a.example/status"), "https://a.example/status", 1000, None, b"").unwrap();
+
+        let requests = requester(RequestSettings::default()).with_cassette(Cassette::replay(&dir).unwrap());
+        let res = requests.get(page).await.unwrap();
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/html");
+        assert_eq!(res.url().as_str(), "https://a.example/page");
+        assert_eq!(res.text().await.unwrap(), "one");
+        let res = requests.get("https://a.example/page?sid=4567").await.unwrap();
+        assert_eq!(res.url().as_str(), "https://a.example/moved");
+        assert_eq!(res.text().await.unwrap(), "two");
+        assert_eq!(requests.get("https://a.example/missing").await.unwrap().status().as_u16(), 404);
+
+        // Hand edited entries fail instead of panicking
+        assert!(matches!(requests.get("https://a.example/broken").await, Err(SessionError::Replay(_))));
+        assert!(matches!(requests.get("https://a.example/status").await, Err(SessionError::Replay(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
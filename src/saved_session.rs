@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+use std::io::Error;
+use serde::{Serialize, Deserialize};
+
+// Cookies of a logged in session, saved so the next run doesn't have to login again
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedSession {
+    pub base_url: String,
+    pub cookies: Vec<(String, String)>, // name, value
+    pub saved: String // epoch
+}
+
+impl SavedSession {
+    pub fn new(base_url: &str, cookies: Vec<(String, String)>) -> SavedSession {
+        let saved = chrono::Utc::now().timestamp().to_string();
+        SavedSession { base_url: base_url.to_string(), cookies, saved }
+    }
+
+    pub fn base_url(&self) -> String { self.base_url.clone() }
+    pub fn cookies(&self) -> Vec<(String, String)> { self.cookies.clone() }
+
+    // Default location of the session file, in the user's data directory
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| home::home_dir().unwrap_or_default())
+            .join("rin_scraper")
+            .join("session.json")
+    }
+
+    pub fn from_file(path: &Path) -> Option<SavedSession> {
+        let f = std::fs::File::open(path).ok()?;
+        // A broken file is the same as no file, we'll just login again
+        serde_json::from_reader(f).ok()
+    }
+    pub fn to_file(&self, path: &Path) -> Result<(), Error> {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+
+        // The cookies log in as the user, only the user should be able to read them
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let f = options.open(path)?;
+        // The mode only applies to new files, a file from an older version keeps its permissions otherwise
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            f.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+
+        serde_json::to_writer_pretty(f, &self)?;
+
+        Ok(())
+    }
+}
+
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn only_the_user_can_read_the_file() {
+        let dir = std::env::temp_dir().join(format!("rin_scraper_session_{}", std::process::id()));
+        let path = dir.join("session.json");
+
+        // Also when the file was already there
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let session = SavedSession::new("https://cs.rin.ru/forum", vec![("phpbb3_rin_sid".to_string(), "abc".to_string())]);
+        session.to_file(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(SavedSession::from_file(&path).unwrap().cookies(), session.cookies());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
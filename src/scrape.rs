@@ -1,278 +1,570 @@
-use std::path::{Path, PathBuf};
-use std::fs::File;
-use std::io::{Write};
-use std::{thread, time};
-use thirtyfour::{*, prelude::*};
-use std::process::{Command, Stdio, Child};
-use serde_json::{Value};
-use regex;
-use terminal_link::Link;
-
-// Info from steam store page, currently only title implemented
-
-#[derive(Debug, Clone)]
-pub struct SteamInfo {
-    title: String,
-    last_update: String,
-    url: String
-}
-
-impl SteamInfo {
-    pub fn title(&self) -> String { self.title.clone() }
-    pub fn last_update(&self) -> String { self.last_update.clone() }
-    pub fn url(&self) -> String { self.url.clone()}
-
-}
-
-// Remove first and last character from string
-fn strip_f_l(value: String) -> String {
-    let mut chars = value.chars();
-    chars.next();
-    chars.next_back();
-    chars.as_str().to_string()
-}
-
-pub struct SearchResult {
-    thread_info: LinkText,
-    author:  String,
-    img_url: String,
-    steam_links: Vec<SteamInfo>,
-    dl_links: Vec<LinkText>
-
-}
-impl SearchResult {
-    pub fn new(thread_info: LinkText, author:  String, img_url: String, steam_links: Vec<SteamInfo>, dl_links: Vec<LinkText>) -> SearchResult {
-        SearchResult { thread_info, author, img_url, steam_links, dl_links }
-    }
-
-    pub fn thread_info(&self) -> LinkText { self.thread_info.clone() }
-    pub fn author(&self) -> String { self.author.clone() }
-    pub fn img_url(&self) -> String { self.img_url.clone() }
-    pub fn steam_links(&self) -> Vec<SteamInfo> { self.steam_links.clone() }
-    pub fn dl_links(&self) -> Vec<LinkText> { self.dl_links.clone() }
-
-}
-
-
-
-
-// A struct for storing link data (link:text)
-#[derive(Clone)]
-pub struct LinkText {
-    link: String,
-    text: String
-}
-impl LinkText {
-    pub fn new(link: &str, text: &str) -> LinkText {
-        LinkText { link: link.to_string(), text: text.to_string() }
-    }
-    pub fn link(&self) -> String { self.link.clone() }
-    pub fn text(&self) -> String { self.text.clone() }
-    pub fn to_hyper(&self) -> String {
-        Link::new(&self.text(), &self.link()).to_string()
-    }
-}
-
-
-// Initialize the WebDriver
-pub async fn browser_init() -> WebDriverResult<(WebDriver, Child)> {
-    let selenium = Command::new("geckodriver.exe")
-        .stdout(Stdio::null())//disable output from the child
-        .spawn()?;
-
-    // Set the capabilities of Firefox
-    let mut caps = DesiredCapabilities::firefox();
-    caps.set_log_level(thirtyfour::common::capabilities::firefox::LogLevel::Fatal)?; // Disable non-fatal logs
-    caps.set_headless()?; // Set as headless
-    caps.add("acceptInsecureCerts", true)?;
-    
-    // Connect to the browser
-    let driver = WebDriver::new("http://127.0.0.1:4444", caps).await?;
-    Ok((driver, selenium))
-}
-
-
-
-// Login to the site using creds from .env file
-pub async fn login(driver: &WebDriver, username: &str, password: &str) -> WebDriverResult<()>{
-    // Load the login page
-    driver.goto("https://cs.rin.ru/forum/ucp.php?mode=login").await?;
-    // Here there's a security check that creates important cookies, that's why geckodriver is used
-
-    // Wait until it can find login form
-    let mut login_form = driver.find(By::Name("username")).await; 
-    while match login_form {
-                Ok(_) => false,
-                Err(_) => true} {
-                    thread::sleep(time::Duration::from_secs_f32(0.2));
-                    login_form = driver.find(By::Name("username")).await;
-                }
-    // Find the forms and Login button
-    let login_form2 = login_form.unwrap();
-    let pass_form = driver.find(By::Name("password")).await?;
-    let login_button = driver.find(By::Name("login")).await?;
-
-    // Enter login info and click the button
-    login_form2.send_keys(username).await?;
-    pass_form.send_keys(password).await?;
-    thread::sleep(time::Duration::from_secs_f32(1.0));
-
-    login_button.click().await?;
-    
-    Ok(())
-
-}
-
-// Search for the query
-pub async fn search(driver: &WebDriver, query: &str) -> WebDriverResult<SearchResult>{
-    //Search for the query in text from first post of threads on SCS forum (id 22)
-    driver.goto(format!("https://cs.rin.ru/forum/search.php?keywords={}&terms=any&author=&fid%5B%5D=22&sc=1&sf=firstpost&sk=t&sd=d&sr=topics&st=0&ch=300&t=0&submit=Search", query)).await?;
-    
-    // Get the first thread and enter it
-    let first = driver.find(By::XPath("/html/body/table/tbody/tr/td/div[2]/form/table[2]/tbody/tr[2]/td[3]/a")).await?;
-    first.click().await?;
-
-    thread::sleep(time::Duration::from_secs_f32(0.5));
-
-    // Get the first post
-    let post = driver.find(By::XPath("/html/body/table/tbody/tr/td/div[2]/div[2]/table[3]/tbody/tr[3]/td[2]/table/tbody/tr/td/div[1]")).await?;
-
-    // Get its title,
-    let title = driver.find(By::XPath("/html/body/table/tbody/tr/td/div[2]/div[1]/h2/a")).await?.text().await?;
-    //  author,
-    let author = driver.find(By::XPath("/html/body/table/tbody/tr/td/div[2]/div[2]/table[3]/tbody/tr[3]/td[1]/table/tbody/tr[1]/td")).await?.text().await?;
-    //  and links
-    let link_elems = post.find_all(By::Tag("a")).await?;
-
-    // Get the url of the thread
-    let url = &driver.current_url().await?.to_string();
-
-    // And create a hyperlink with the thread title as text
-    let thread_info: LinkText = LinkText::new(url, &title);
-
-
-
-    let mut steam_links: Vec<String> = Vec::new();
-    let mut dl_links: Vec<LinkText> = Vec::new();
-    
-
-    // for each extracted links
-    for link in link_elems {
-        let href = link.attr("href").await?.unwrap();
-
-        // Get the steam links
-        if href.contains("store.steampowered.com") {
-            steam_links.push(href.clone());
-        }
-        // And the download links
-        if href.contains("privatebin.rinuploads.org") || href.contains("drive.google.com") || href.contains("filecrypt.cc") {
-            dl_links.push(LinkText::new(&href.clone(), &link.text().await?));
-        }
-    };
-
-
-    
-    
-    // Get the url of the first image in post
-    let img_url = post.find(By::Tag("img")).await?.attr("src").await?.expect("Couldnt find image url");
-
- 
-
-    
-    let mut steam: Vec<SteamInfo> = Vec::new();
-
-    for link in steam_links {
-        let info = steam_info(&link).await?;
-        steam.push(info);
-    }
-
-
-
-
-
-    Ok(SearchResult::new(thread_info, author, img_url, steam, dl_links))
-}
-
-
-
-
-// Used for downloading a file and saving it to TEMP dir 
-pub async fn download_file(url: &str, tmp_dir: &Path) -> PathBuf {
-
-    
-    // Send a request for the file
-    let file = reqwest::get(url).await.expect("URL doesn't exist");
-
-    // Get file name of the file
-    let fname = file
-            .url()
-            .path_segments()
-            .and_then(|segments| segments.last())
-            .and_then(|name| if name.is_empty() { None } else { Some(name) })
-            .unwrap_or("tmp.bin");
-
-    // Full path of the downloaded file
-    let file_path = tmp_dir.join(fname);
-
-
-
-
-    // Create th file
-    let mut dest = File::create(file_path.clone()).expect("Can't create file while downloading");
-    // Get its contents from the link
-    let content =  file.bytes().await.expect("Couldn't get bytes");
-    // And write them to the file
-    dest.write_all(&content).expect("Cant copy file to destination");
-
-    return file_path //Return path of the file
-}
-
-
-
-
-
-
-// Get info from steam page
-async fn steam_info(url: &str) -> Result<SteamInfo, std::io::Error> {
-
-    let reg = regex::Regex::new(r"https://store\.steampowered\.com/app/([[:digit:]]+)*").unwrap();
-    let appid =  &reg.captures(url).unwrap()[1];
-
-
-    let res = reqwest::get(format!("http://api.steamcmd.net/v1/info/{}", appid)).await.expect("Couldnt get response from SteamCMD");
-
-    let parsed: Value = serde_json::from_str(&res.text().await.unwrap())?;
-
-
-
-    let title = strip_f_l(parsed["data"][appid]["common"]["name"].to_string());  
-    
-    let mut last_update = "0".to_string();
-
-
-    for branches in parsed
-            ["data"]
-            [appid]
-            ["depots"]
-            ["branches"].as_object() {  
-                for value in branches.values(){
-                    let db_time = strip_f_l(value["timeupdated"].to_string()).parse::<i32>();
-                    let time: i32 = match db_time {
-                        Ok(res) => res,
-                        Err(_) => 0
-                    };
-                    
-                    if time > last_update.parse::<i32>().unwrap() {
-                        last_update = time.to_string();
-                    }
-                }
-    }
-    let info = SteamInfo {
-        title,
-        last_update: last_update,
-        url: url.to_string()
-    };
-    
-    Ok(info)
-}
-
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::{Write};
+use std::{thread, time};
+use thirtyfour::{*, prelude::*};
+use std::process::{Command, Stdio, Child};
+use terminal_link::Link;
+
+use crate::backend::{ForumBackend, BackendResult, BackendError, wait_for, join_url};
+use crate::parser::{self, TopicHit, Post};
+use crate::session::SessionError;
+use crate::saved_session::SavedSession;
+use crate::query::SearchQuery;
+use crate::request::Requester;
+use crate::settings::ProxySettings;
+use crate::steam::{self, SteamInfo};
+use crate::steam_provider::SteamMetadata;
+use crate::hosts::{HostRegistry, LinkKind};
+
+pub struct SearchResult {
+    thread_info: LinkText,
+    author:  String,
+    img_url: Option<String>,
+    steam_links: Vec<SteamInfo>,
+    dl_links: Vec<LinkText>,
+    posts: Vec<Post>
+
+}
+impl SearchResult {
+    pub fn new(thread_info: LinkText, author:  String, img_url: Option<String>, steam_links: Vec<SteamInfo>, dl_links: Vec<LinkText>) -> SearchResult {
+        SearchResult { thread_info, author, img_url, steam_links, dl_links, posts: Vec::new() }
+    }
+
+    // Add the download links from other posts of the topic, links that are already there are skipped
+    pub fn merge_posts(&mut self, posts: &[Post], hosts: &HostRegistry) {
+        for post in posts {
+            for link in post.links() {
+                if self.dl_links.iter().any(|l| l.link() == link.link()) {
+                    continue;
+                }
+                if let Some(kind) = hosts.classify(&link.link(), &link.text()) {
+                    self.dl_links.push(link.with_source(&post.post_id()).with_kind(kind));
+                }
+            }
+            self.posts.push(post.clone());
+        }
+    }
+
+    pub fn posts(&self) -> Vec<Post> { self.posts.clone() }
+
+    pub fn thread_info(&self) -> LinkText { self.thread_info.clone() }
+    pub fn author(&self) -> String { self.author.clone() }
+    pub fn img_url(&self) -> Option<String> { self.img_url.clone() }
+    pub fn steam_links(&self) -> Vec<SteamInfo> { self.steam_links.clone() }
+    pub fn dl_links(&self) -> Vec<LinkText> { self.dl_links.clone() }
+
+}
+
+
+
+
+// A struct for storing link data (link:text) and the id of the post it came from
+#[derive(Debug, Clone)]
+pub struct LinkText {
+    link: String,
+    text: String,
+    source: Option<String>,
+    kind: Option<LinkKind> // for download links
+}
+impl LinkText {
+    pub fn new(link: &str, text: &str) -> LinkText {
+        LinkText { link: link.to_string(), text: text.to_string(), source: None, kind: None }
+    }
+    pub fn with_source(self, post_id: &str) -> LinkText {
+        LinkText { source: Some(post_id.to_string()), ..self }
+    }
+    pub fn with_kind(self, kind: LinkKind) -> LinkText {
+        LinkText { kind: Some(kind), ..self }
+    }
+    pub fn link(&self) -> String { self.link.clone() }
+    pub fn text(&self) -> String { self.text.clone() }
+    pub fn source(&self) -> Option<String> { self.source.clone() }
+    pub fn kind(&self) -> Option<LinkKind> { self.kind }
+    pub fn to_hyper(&self) -> String {
+        Link::new(&self.text(), &self.link()).to_string()
+    }
+}
+
+
+// Initialize the WebDriver
+pub async fn browser_init(proxy: &ProxySettings) -> WebDriverResult<(WebDriver, Child)> {
+    let selenium = Command::new("geckodriver.exe")
+        .stdout(Stdio::null())//disable output from the child
+        .spawn()?;
+
+    // Set the capabilities of Firefox
+    let mut caps = DesiredCapabilities::firefox();
+    caps.set_log_level(thirtyfour::common::capabilities::firefox::LogLevel::Fatal)?; // Disable non-fatal logs
+    caps.set_headless()?; // Set as headless
+    caps.add("acceptInsecureCerts", true)?;
+    if let Some(pac) = proxy_autoconfig(proxy) {
+        caps.set_proxy(Proxy::AutoConfig { url: pac })?;
+    }
+    
+    // Connect to the browser
+    let driver = WebDriver::new("http://127.0.0.1:4444", caps).await?;
+    Ok((driver, selenium))
+}
+
+// Firefox can't have a proxy per host in its settings, but it can with a proxy auto-config script,
+// which is passed as a data url
+fn proxy_autoconfig(proxy: &ProxySettings) -> Option<String> {
+    if proxy.proxies().is_empty() {
+        return None;
+    }
+
+    // PAC wants e.g. "SOCKS5 host:port" instead of a url
+    let pac_entry = |p: Option<String>| -> String {
+        let url = match p.and_then(|p| reqwest::Url::parse(&p).ok()) {
+            Some(url) => url,
+            None => return "DIRECT".to_string()
+        };
+        let kind = match url.scheme() {
+            "socks5" | "socks5h" => "SOCKS5",
+            "socks4" | "socks4a" => "SOCKS",
+            "https" => "HTTPS",
+            _ => "PROXY"
+        };
+        format!("{} {}:{}", kind, url.host_str().unwrap_or(""), url.port_or_known_default().unwrap_or(1080))
+    };
+
+    let mut script = "function FindProxyForURL(url, host) {\n".to_string();
+    // Most specific hosts first, like ProxySettings::for_host
+    let mut hosts: Vec<(&String, &String)> = proxy.hosts.iter().collect();
+    hosts.sort_by_key(|(h, _)| std::cmp::Reverse(h.len()));
+    for (host, p) in hosts {
+        script.push_str(&format!("  if (host == \"{0}\" || dnsDomainIs(host, \".{0}\")) return \"{1}\";\n", host, pac_entry(Some(p.clone()))));
+    }
+    script.push_str(&format!("  return \"{}\";\n}}", pac_entry(proxy.url.clone())));
+
+    Some(format!("data:application/x-ns-proxy-autoconfig;base64,{}", base64::encode(script)))
+}
+
+
+// Login to the site using creds from .env file
+pub async fn login(backend: &mut dyn ForumBackend, username: &str, password: &str) -> BackendResult<()>{
+    // Load the login page
+    backend.navigate(&backend.url("ucp.php?mode=login")).await?;
+    // Here there's a security check that creates important cookies, wait until it's done and the login form shows up
+    wait_for(backend, "input[name=username]", time::Duration::from_secs(30)).await?;
+
+    // phpBB rejects forms that are submitted too quickly
+    thread::sleep(time::Duration::from_secs_f32(1.0));
+
+    // Enter login info and click the button
+    backend.submit_form(&[("input[name=username]", username), ("input[name=password]", password)], "input[name=login]").await?;
+
+    let page = backend.page_source().await?;
+    if !is_logged_in(&page) {
+        let msg = backend.find(".error").await.map(|e| e.text()).unwrap_or_else(|_| "wrong username or password".to_string());
+        return Err(BackendError::LoginFailed(msg));
+    }
+
+    Ok(())
+
+}
+
+// A logged in user always has a logout link
+pub fn is_logged_in(page: &str) -> bool {
+    page.contains("mode=logout")
+}
+
+// Put the cookies from the last run into the backend, returns false if there was no session for this forum
+pub async fn restore_session(backend: &mut dyn ForumBackend, path: &Path) -> BackendResult<bool> {
+    match SavedSession::from_file(path) {
+        Some(saved) if saved.base_url() == backend.base_url() => {
+            backend.add_cookies(&saved.cookies()).await?;
+            Ok(true)
+        }
+        _ => Ok(false)
+    }
+}
+
+// Save the cookies of the backend so the next run can reuse them
+pub async fn save_session(backend: &dyn ForumBackend, path: &Path) -> BackendResult<()> {
+    SavedSession::new(&backend.base_url(), backend.cookies().await?).to_file(path)?;
+    Ok(())
+}
+
+// Search for the query and get every topic found, following up to max_pages pages of results
+pub async fn search_topics(backend: &mut dyn ForumBackend, query: &SearchQuery, max_pages: usize) -> BackendResult<Vec<TopicHit>> {
+    let mut hits: Vec<TopicHit> = Vec::new();
+    let mut start = 0;
+
+    for _ in 0..max_pages {
+        backend.navigate(&backend.url(&query.path(start))).await?;
+        let page = backend.page_source().await?;
+        let url = backend.current_url().await?;
+
+        // The session expired, or the saved one was logged out
+        if !is_logged_in(&page) {
+            return Err(BackendError::LoggedOut);
+        }
+
+        for hit in parser::topic_hits(&page) {
+            // Results can shift between pages while we're reading them
+            if hits.iter().any(|h| h.topic_id() == hit.topic_id()) {
+                continue;
+            }
+            // Make the links absolute so they can be opened from anywhere
+            let href = join_url(&url, &hit.href())?;
+            hits.push(hit.with_href(&href));
+        }
+
+        match parser::next_page_start(&page, "search.php", start, None) {
+            Some(next) => start = next,
+            None => break
+        }
+    }
+
+    Ok(hits)
+}
+
+// Get every post of the topic, following up to max_pages pages
+pub async fn crawl_topic(backend: &mut dyn ForumBackend, topic: &str, max_pages: usize) -> BackendResult<Vec<Post>> {
+    let mut posts: Vec<Post> = Vec::new();
+    let mut start = 0;
+    let topic_id = parser::url_param(topic, "t");
+
+    for _ in 0..max_pages {
+        backend.navigate(&with_start(topic, start)?).await?;
+        let page = backend.page_source().await?;
+
+        if !is_logged_in(&page) {
+            return Err(BackendError::LoggedOut);
+        }
+
+        // Attachments have links relative to the page
+        let page_url = backend.current_url().await?;
+        for post in parser::posts(&page) {
+            if !posts.iter().any(|p| p.post_id() == post.post_id()) {
+                posts.push(post.with_base(&page_url));
+            }
+        }
+
+        match parser::next_page_start(&page, "viewtopic.php", start, topic_id.as_deref()) {
+            Some(next) => start = next,
+            None => break
+        }
+    }
+
+    Ok(posts)
+}
+
+// Url with the start= parameter set, used for going through pages
+fn with_start(url: &str, start: u32) -> BackendResult<String> {
+    let mut url = reqwest::Url::parse(url).map_err(|e| SessionError::Parse(e.to_string()))?;
+    let pairs: Vec<(String, String)> = url.query_pairs()
+        .filter(|(k, _)| k != "start")
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    if start > 0 {
+        url.query_pairs_mut().append_pair("start", &start.to_string());
+    }
+    Ok(url.to_string())
+}
+
+// Open the topic and get the info from its first post, download links from the rest of the posts are added to it
+pub async fn open_topic(backend: &mut dyn ForumBackend, hit: &TopicHit, max_pages: usize, steam: &SteamMetadata, hosts: &HostRegistry) -> BackendResult<SearchResult>{
+    let topic = hit.href();
+    let posts = crawl_topic(backend, &topic, max_pages).await?;
+    let page = backend.page_source().await?;
+
+    // Get the first post
+    let post = posts.first()
+        .ok_or_else(|| BackendError::NotFound(format!("posts in {}", topic)))?;
+
+    // Get its title,
+    let title = parser::topic_title(&page).unwrap_or_else(|| hit.title());
+    //  author,
+    let author = post.author();
+    //  and links
+    let links = post.links();
+
+    // Create a hyperlink with the thread title as text
+    let thread_info: LinkText = LinkText::new(&topic, &title);
+
+    // Get the url of the first image in post, not every post has one
+    let img_url = post.images().into_iter().next();
+
+
+    let mut steam_links: Vec<String> = Vec::new();
+    let mut dl_links: Vec<LinkText> = Vec::new();
+    
+
+    // for each extracted links
+    for link in links {
+        let href = link.link();
+
+        // Get the steam links
+        if href.contains("store.steampowered.com") {
+            steam_links.push(href.clone());
+        }
+        // And the download links
+        if let Some(kind) = hosts.classify(&href, &link.text()) {
+            dl_links.push(link.with_source(&post.post_id()).with_kind(kind));
+        }
+    };
+
+    let mut steam_infos: Vec<SteamInfo> = Vec::new();
+
+    // Links to subs and bundles don't have an app id.
+    // The topic is still worth showing when Steam can't be reached
+    for app_id in steam_links.iter().filter_map(|link| steam::app_id(link)) {
+        match steam.app_info(&backend.requests(), &app_id).await {
+            Ok(info) => steam_infos.push(info),
+            Err(e) => println!("Warning: no Steam info for app {}, showing the topic without it ({})", app_id, e)
+        }
+    }
+
+    let mut result = SearchResult::new(thread_info, author, img_url, steam_infos, dl_links);
+    result.merge_posts(&posts, hosts);
+    Ok(result)
+}
+
+
+// Used for downloading a file and saving it to TEMP dir 
+pub async fn download_file(requests: &Requester, url: &str, tmp_dir: &Path) -> BackendResult<PathBuf> {
+
+    
+    // Send a request for the file
+    let file = requests.get(url).await?.error_for_status()?;
+
+    // Get file name of the file
+    let fname = file
+            .url()
+            .path_segments()
+            .and_then(|segments| segments.last())
+            .and_then(|name| if name.is_empty() { None } else { Some(name) })
+            .unwrap_or("tmp.bin");
+
+    // Full path of the downloaded file
+    let file_path = tmp_dir.join(fname);
+
+
+
+
+    // Create th file
+    let mut dest = File::create(file_path.clone())?;
+    // Get its contents from the link
+    let content =  file.bytes().await?;
+    // And write them to the file
+    dest.write_all(&content)?;
+
+    Ok(file_path) //Return path of the file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::HttpBackend;
+    use crate::mock_server::{MockServer, MockResponse};
+    use crate::session::HttpSession;
+    use crate::settings::SteamSettings;
+    use crate::steam_cache::SteamCache;
+    use crate::steam_provider::{self, SteamMetadata};
+
+    // The security check sets a cookie with javascript before the login form shows up
+    async fn forum(post: &str) -> (MockServer, HttpBackend) {
+        let server = MockServer::start().await;
+        server.route("GET", "/ucp.php", vec![
+            MockResponse::page(include_str!("../tests/fixtures/login_security_check.html")),
+            MockResponse::page(include_str!("../tests/fixtures/login.html"))
+        ]);
+        server.route("POST", "/ucp.php", vec![MockResponse::page(post)]);
+        let backend = HttpBackend::new(HttpSession::new(&server.url(), server.requester()).unwrap());
+        (server, backend)
+    }
+
+    #[tokio::test]
+    async fn login_submits_the_form() {
+        let (server, mut backend) = forum(include_str!("../tests/fixtures/index_logged_in.html")).await;
+        login(&mut backend, "example", "hunter2").await.unwrap();
+
+        let requests = server.requests();
+        let methods: Vec<&str> = requests.iter().map(|r| r.method.as_str()).collect();
+        assert_eq!(methods, vec!["GET", "GET", "POST"]);
+        // The cookie from the security check is sent from then on
+        assert!(!requests[0].headers.contains_key("cookie"));
+        assert!(requests[1].headers["cookie"].contains("scs_check=8f3a1c0e5b"));
+        assert!(requests[2].headers["cookie"].contains("scs_check=8f3a1c0e5b"));
+
+        let post = &requests[2];
+        assert_eq!(post.path, "/ucp.php?mode=login&sid=0123456789abcdef0123456789abcdef");
+        assert_eq!(post.form_value("username").as_deref(), Some("example"));
+        assert_eq!(post.form_value("password").as_deref(), Some("hunter2"));
+        assert_eq!(post.form_value("login").as_deref(), Some("Login"));
+        assert_eq!(post.form_value("redirect").as_deref(), Some("index.php"));
+        assert_eq!(post.form_value("sid").as_deref(), Some("0123456789abcdef0123456789abcdef"));
+        assert_eq!(post.form_value("creation_time").as_deref(), Some("1666083660"));
+        assert_eq!(post.form_value("form_token").as_deref(), Some("5c2a1c7d0f8e4b3a9e6d2f1a0b9c8d7e6f5a4b3c"));
+        // Unchecked boxes aren't sent
+        assert_eq!(post.form_value("autologin"), None);
+
+        let cookies = backend.cookies().await.unwrap();
+        assert!(cookies.contains(&("scs_check".to_string(), "8f3a1c0e5b".to_string())));
+    }
+
+    #[tokio::test]
+    async fn login_reports_the_error() {
+        let (_server, mut backend) = forum(include_str!("../tests/fixtures/login_failed.html")).await;
+        match login(&mut backend, "example", "wrong").await {
+            Err(BackendError::LoginFailed(msg)) => assert!(msg.starts_with("You have specified an incorrect password"), "{}", msg),
+            res => panic!("expected a failed login, got {:?}", res.map(|_| ()))
+        }
+    }
+
+    #[tokio::test]
+    async fn topic_opens_without_steam() {
+        let server = MockServer::start().await;
+        server.route("GET", "/viewtopic.php", vec![MockResponse::page(include_str!("../tests/fixtures/viewtopic.html"))]);
+        let mut backend = HttpBackend::new(HttpSession::new(&server.url(), server.requester()).unwrap());
+        // Nothing answers on the steamcmd url, and nothing is cached
+        let settings = SteamSettings { providers: vec!["steamcmd".to_string()], steamcmd_url: server.url(), ..SteamSettings::default() };
+        let steam = SteamMetadata::new(steam_provider::from_settings(&settings).unwrap(), SteamCache::disabled());
+
+        let hit = parser::topic_hits(include_str!("../tests/fixtures/search_results.html")).remove(0)
+            .with_href(&format!("{}/viewtopic.php?f=22&t=63410", server.url()));
+        let res = open_topic(&mut backend, &hit, 1, &steam, &HostRegistry::defaults()).await.unwrap();
+        assert!(res.steam_links().is_empty());
+        assert_eq!(res.thread_info().text(), "The Witcher 3: Wild Hunt [Win64] [Multi-Language]");
+        assert!(!res.dl_links().is_empty());
+        assert!(server.requests().iter().any(|r| r.path == "/info/292030"));
+    }
+
+    // A subsilver2 topic page with a post for each (post id, link), and a link to the next page
+    fn topic_page(posts: &[(&str, &str)], next: Option<u32>) -> String {
+        let pages = next.map(|st| format!(r#"<a href="./viewtopic.php?f=22&amp;t=63410&amp;start={st}">Next</a>"#)).unwrap_or_default();
+        let posts: String = posts.iter().map(|(id, link)| format!(
+            r#"<a name="p{id}"></a><b class="postauthor">Uploader</b><div class="postbody">{link}</div>"#
+        )).collect();
+        format!(r#"<html><body><a href="./ucp.php?mode=logout">Logout</a><h2><a href="./viewtopic.php?t=63410">Game</a></h2>{pages}{posts}</body></html>"#)
+    }
+
+    #[tokio::test]
+    async fn crawls_every_page_of_the_topic() {
+        let server = MockServer::start().await;
+        server.route("GET", "/viewtopic.php", vec![
+            MockResponse::page(&topic_page(&[
+                ("1", r#"<a href="https://store.steampowered.com/bundle/1/">Bundle</a><a href="https://mega.nz/file/a">Game | 01.02.2022</a>"#)
+            ], Some(15))),
+            MockResponse::page(&topic_page(&[("16", r#"<a href="https://mega.nz/file/b">Game | 05.02.2022</a>"#)], Some(30))),
+            MockResponse::page(&topic_page(&[
+                ("31", r#"<a href="https://mega.nz/file/b">Game | 05.02.2022</a><a href="https://gofile.io/d/c">Game | 09.02.2022</a>"#),
+                // Pages of other topics aren't pages of this one
+                ("32", r#"<a href="./viewtopic.php?t=99&amp;start=3000">Old topic</a>"#)
+            ], None))
+        ]);
+        let mut backend = HttpBackend::new(HttpSession::new(&server.url(), server.requester()).unwrap());
+        let steam = SteamMetadata::new(steam_provider::from_settings(&SteamSettings::default()).unwrap(), SteamCache::disabled());
+        let hit = parser::topic_hits(include_str!("../tests/fixtures/search_results.html")).remove(0)
+            .with_href(&format!("{}/viewtopic.php?f=22&t=63410", server.url()));
+
+        let res = open_topic(&mut backend, &hit, 50, &steam, &HostRegistry::defaults()).await.unwrap();
+        let paths: Vec<String> = server.requests().iter().map(|r| r.path.clone()).collect();
+        assert_eq!(paths, vec!["/viewtopic.php?f=22&t=63410", "/viewtopic.php?f=22&t=63410&start=15", "/viewtopic.php?f=22&t=63410&start=30"]);
+
+        // The same link posted again keeps the post it was first in
+        let links: Vec<(String, Option<String>)> = res.dl_links().iter().map(|l| (l.link(), l.source())).collect();
+        assert_eq!(links, vec![
+            ("https://mega.nz/file/a".to_string(), Some("1".to_string())),
+            ("https://mega.nz/file/b".to_string(), Some("16".to_string())),
+            ("https://gofile.io/d/c".to_string(), Some("31".to_string()))
+        ]);
+        let posts: Vec<String> = res.posts().iter().map(|p| p.post_id()).collect();
+        assert_eq!(posts, vec!["1", "16", "31", "32"]);
+    }
+
+    #[test]
+    fn sets_the_start_of_the_page() {
+        assert_eq!(with_start("https://cs.rin.ru/forum/viewtopic.php?f=22&t=63410&start=15", 30).unwrap(), "https://cs.rin.ru/forum/viewtopic.php?f=22&t=63410&start=30");
+        assert_eq!(with_start("https://cs.rin.ru/forum/viewtopic.php?start=15&t=63410", 0).unwrap(), "https://cs.rin.ru/forum/viewtopic.php?t=63410");
+        assert!(with_start("viewtopic.php?t=1", 15).is_err());
+    }
+
+    #[test]
+    fn builds_the_proxy_script() {
+        assert_eq!(proxy_autoconfig(&ProxySettings::default()), None);
+
+        let settings = ProxySettings {
+            url: Some("http://proxy:8080".to_string()),
+            hosts: [
+                ("rin.ru", "socks5h://127.0.0.1:9050"),
+                ("cs.rin.ru", "socks4://127.0.0.1"),
+                ("mega.nz", "https://secure.example:8443"),
+                ("steampowered.com", "direct")
+            ].iter().map(|(h, p)| (h.to_string(), p.to_string())).collect()
+        };
+        let url = proxy_autoconfig(&settings).unwrap();
+        let script = url.strip_prefix("data:application/x-ns-proxy-autoconfig;base64,").unwrap();
+        let script = String::from_utf8(base64::decode(script).unwrap()).unwrap();
+        let lines: Vec<&str> = script.lines().collect();
+
+        assert_eq!(lines[0], "function FindProxyForURL(url, host) {");
+        // Subdomains are checked before their domains
+        let cs = lines.iter().position(|l| l.contains("\"cs.rin.ru\"")).unwrap();
+        let rin = lines.iter().position(|l| l.contains("\"rin.ru\"")).unwrap();
+        assert!(cs < rin);
+        assert_eq!(lines[cs], "  if (host == \"cs.rin.ru\" || dnsDomainIs(host, \".cs.rin.ru\")) return \"SOCKS 127.0.0.1:1080\";");
+        assert_eq!(lines[rin], "  if (host == \"rin.ru\" || dnsDomainIs(host, \".rin.ru\")) return \"SOCKS5 127.0.0.1:9050\";");
+        assert!(lines.contains(&"  if (host == \"mega.nz\" || dnsDomainIs(host, \".mega.nz\")) return \"HTTPS secure.example:8443\";"));
+        assert!(lines.contains(&"  if (host == \"steampowered.com\" || dnsDomainIs(host, \".steampowered.com\")) return \"DIRECT\";"));
+        assert_eq!(&lines[lines.len() - 2..], &["  return \"PROXY proxy:8080\";", "}"]);
+
+        // Only some hosts go through a proxy
+        let settings = ProxySettings { url: None, hosts: [("rin.ru".to_string(), "http://proxy".to_string())].into_iter().collect() };
+        let script = base64::decode(proxy_autoconfig(&settings).unwrap().rsplit(',').next().unwrap()).unwrap();
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("return \"PROXY proxy:80\";"));
+        assert!(script.ends_with("  return \"DIRECT\";\n}"));
+    }
+
+    // A subsilver2 search results page with the topics (id, title), and a link to the next page
+    fn search_page(topics: &[(&str, &str)], next: Option<u32>) -> String {
+        let pages = next.map(|st| format!(r#"<a href="./search.php?keywords=witcher+3&amp;start={st}">Next</a>"#)).unwrap_or_default();
+        let rows: String = topics.iter().map(|(id, title)| format!(
+            r#"<tr><td class="row1"><a href="./viewtopic.php?f=22&amp;t={id}" class="topictitle">{title}</a></td><td><p class="topicauthor">Uploader</p></td></tr>"#
+        )).collect();
+        format!(r#"<html><body><a href="./ucp.php?mode=logout">Logout</a>{pages}<table>{rows}</table></body></html>"#)
+    }
+
+    #[tokio::test]
+    async fn searches_every_page() {
+        let server = MockServer::start().await;
+        server.route("GET", "/search.php", vec![
+            MockResponse::page(include_str!("../tests/fixtures/search_results.html")),
+            // A new topic pushed the last one of the first page to this one
+            MockResponse::page(&search_page(&[("98127", "Witcher 3 crashes on start"), ("70000", "The Witcher 3 Mods")], Some(50))),
+            MockResponse::page(&search_page(&[("80000", "The Witcher 3 Soundtrack")], None))
+        ]);
+        let mut backend = HttpBackend::new(HttpSession::new(&server.url(), server.requester()).unwrap());
+        let query = SearchQuery::new("witcher 3");
+
+        let hits = search_topics(&mut backend, &query, 10).await.unwrap();
+        let ids: Vec<String> = hits.iter().map(|h| h.topic_id()).collect();
+        assert_eq!(ids, vec!["63410", "98127", "70000", "80000"]);
+        assert_eq!(hits[2].href(), format!("{}/viewtopic.php?f=22&t=70000", server.url()));
+        // The session id from the first page is sent from then on
+        let paths: Vec<String> = server.requests().iter().map(|r| r.path.replace("&sid=fedcba9876543210fedcba9876543210", "")).collect();
+        assert_eq!(paths, vec![format!("/{}", query.path(0)), format!("/{}", query.path(25)), format!("/{}", query.path(50))]);
+    }
+
+    #[tokio::test]
+    async fn stops_searching_at_max_pages() {
+        let server = MockServer::start().await;
+        server.route("GET", "/search.php", vec![MockResponse::page(&search_page(&[("1", "Game")], Some(25)))]);
+        let mut backend = HttpBackend::new(HttpSession::new(&server.url(), server.requester()).unwrap());
+
+        let hits = search_topics(&mut backend, &SearchQuery::new("game"), 1).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(server.requests().len(), 1);
+
+        // A page without the logout link means the session is gone
+        server.route("GET", "/search.php", vec![MockResponse::page("<html><body>Login</body></html>")]);
+        assert!(matches!(search_topics(&mut backend, &SearchQuery::new("game"), 3).await, Err(BackendError::LoggedOut)));
+    }
+}
@@ -0,0 +1,147 @@
+use std::{fmt, sync::Arc};
+use reqwest::{Client, RequestBuilder, cookie::{Jar, CookieStore}, Url};
+use regex::Regex;
+
+use crate::request::Requester;
+
+// Default address of the forum
+pub const FORUM_URL: &str = "https://cs.rin.ru/forum";
+
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:104.0) Gecko/20100101 Firefox/104.0";
+
+
+// Errors that can happen while talking to the forum over plain HTTP
+#[derive(Debug)]
+pub enum SessionError {
+    Http(reqwest::Error),
+    Parse(String),
+    Replay(String) // a cassette entry that can't be turned back into a response
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Http(e) => write!(f, "HTTP error: {}", e),
+            SessionError::Parse(msg) => write!(f, "Couldn't parse the page: {}", msg),
+            SessionError::Replay(msg) => write!(f, "Couldn't replay {}", msg)
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<reqwest::Error> for SessionError {
+    fn from(e: reqwest::Error) -> SessionError { SessionError::Http(e) }
+}
+
+
+// Authenticated forum session over HTTP, a replacement for logging in through geckodriver
+pub struct HttpSession {
+    client: Client,
+    jar: Arc<Jar>,
+    base_url: String,
+    sid: Option<String>,
+    requests: Requester
+}
+
+impl HttpSession {
+    pub fn new(base_url: &str, requests: Requester) -> Result<HttpSession, SessionError> {
+        let jar = Arc::new(Jar::default());
+        let client = requests.client_builder()
+            .cookie_provider(jar.clone())
+            .user_agent(USER_AGENT)
+            .build()?;
+
+        Ok(HttpSession { client, jar, base_url: base_url.trim_end_matches('/').to_string(), sid: None, requests })
+    }
+
+
+    pub fn base_url(&self) -> String { self.base_url.clone() }
+    pub fn requests(&self) -> Requester { self.requests.clone() }
+
+    // Full url of a forum page, with the session id appended once we have one
+    pub fn url(&self, path: &str) -> String {
+        let url = format!("{}/{}", self.base_url, path.trim_start_matches('/'));
+        match &self.sid {
+            Some(sid) if !url.contains("sid=") => {
+                let sep = if url.contains('?') { '&' } else { '?' };
+                format!("{url}{sep}sid={sid}")
+            }
+            _ => url
+        }
+    }
+
+    // Cookies the forum has set, as name and value
+    pub fn cookies(&self) -> Result<Vec<(String, String)>, SessionError> {
+        let url = Url::parse(&self.base_url).map_err(|e| SessionError::Parse(e.to_string()))?;
+        let header = match self.jar.cookies(&url) {
+            Some(header) => header.to_str().unwrap_or("").to_string(),
+            None => return Ok(Vec::new())
+        };
+
+        Ok(header.split("; ")
+            .filter_map(|c| c.split_once('='))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect())
+    }
+
+    // Put cookies from an earlier session into the jar
+    pub fn add_cookies(&self, cookies: &[(String, String)]) -> Result<(), SessionError> {
+        let url = Url::parse(&self.base_url).map_err(|e| SessionError::Parse(e.to_string()))?;
+        for (name, value) in cookies {
+            self.jar.add_cookie_str(&format!("{name}={value}; Path=/"), &url);
+        }
+        Ok(())
+    }
+
+    // GET a page and return the url it ended up at after redirects, and its source
+    pub async fn get(&mut self, url: &str) -> Result<(String, String), SessionError> {
+        let (mut final_url, mut page) = self.fetch(self.client.get(url)).await?;
+
+        // The security check page sets a cookie with javascript and reloads, so set it ourselves
+        if self.pass_security_check(&page)? {
+            (final_url, page) = self.fetch(self.client.get(url)).await?;
+        }
+
+        self.update_sid(&page);
+        Ok((final_url, page))
+    }
+
+    // POST a form and return the url and source of the resulting page
+    pub async fn post_form(&mut self, url: &str, fields: &[(String, String)]) -> Result<(String, String), SessionError> {
+        let (final_url, page) = self.fetch(self.client.post(url).form(fields)).await?;
+        self.update_sid(&page);
+        Ok((final_url, page))
+    }
+
+    // Send the request through the request layer and get the final url and the page
+    async fn fetch(&self, request: RequestBuilder) -> Result<(String, String), SessionError> {
+        let res = self.requests.send(&self.client, request.build()?).await?.error_for_status()?;
+        let url = res.url().to_string();
+        Ok((url, res.text().await?))
+    }
+
+    // Copy the cookies set by the security check script into the jar, returns if there were any
+    fn pass_security_check(&self, page: &str) -> Result<bool, SessionError> {
+        let re = Regex::new(r#"document\.cookie\s*=\s*["']([^"']+)["']"#).unwrap();
+        let url = Url::parse(&self.base_url).map_err(|e| SessionError::Parse(e.to_string()))?;
+
+        let mut found = false;
+        for cap in re.captures_iter(page) {
+            self.jar.add_cookie_str(&cap[1], &url);
+            found = true;
+        }
+
+        Ok(found)
+    }
+
+    // phpBB puts the session id in links until it knows that cookies work
+    fn update_sid(&mut self, page: &str) {
+        let re = Regex::new(r"[?&](?:amp;)?sid=([0-9a-f]{32})").unwrap();
+        if let Some(cap) = re.captures(page) {
+            self.sid = Some(cap[1].to_string());
+        }
+    }
+}
+
+
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+
+use crate::hosts::{LinkKind, Support, Resolver};
+
+// Settings that aren't tied to a game, read from settings.json in the user's config directory.
+// Every field has a default, so the file only needs the ones that are changed
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub requests: RequestSettings,
+    pub proxy: ProxySettings,
+    pub steam: SteamSettings,
+    pub hosts: HashMap<String, HostSettings> // domain, changes to the default hosts or a new one
+}
+
+// Limits for the requests we send, so we don't hammer the forum or the other sites
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RequestSettings {
+    pub min_delay_ms: u64, // between two requests to the same host
+    pub host_delay_ms: HashMap<String, u64>, // host, delay - overrides min_delay_ms
+    pub retries: u32,
+    pub backoff_ms: u64, // first wait before a retry, doubled for every next one
+    pub max_backoff_ms: u64,
+    pub log: bool // print every request and retry
+}
+
+impl Default for RequestSettings {
+    fn default() -> RequestSettings {
+        RequestSettings {
+            min_delay_ms: 1000,
+            host_delay_ms: HashMap::new(),
+            retries: 3,
+            backoff_ms: 1000,
+            max_backoff_ms: 30000,
+            log: false
+        }
+    }
+}
+
+// Proxy for every request and the browser, e.g. socks5h://127.0.0.1:9050 or http://proxy:8080
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub url: Option<String>, // for every host that isn't in hosts
+    pub hosts: HashMap<String, String> // host, proxy url or "direct"
+}
+
+impl ProxySettings {
+    // Proxy url for the host, None if it's reached directly
+    pub fn for_host(&self, host: &str) -> Option<String> {
+        // The most specific host wins, so a subdomain can have a different proxy
+        let proxy = self.hosts.iter()
+            .filter(|(h, _)| host == h.as_str() || host.ends_with(&format!(".{}", h)))
+            .max_by_key(|(h, _)| h.len())
+            .map(|(_, p)| p.clone())
+            .or_else(|| self.url.clone())?;
+        Some(proxy).filter(|p| !p.eq_ignore_ascii_case("direct"))
+    }
+
+    // Every proxy that's used
+    pub fn proxies(&self) -> Vec<String> {
+        let mut proxies: Vec<String> = self.url.iter().chain(self.hosts.values())
+            .filter(|p| !p.eq_ignore_ascii_case("direct"))
+            .cloned()
+            .collect();
+        proxies.sort();
+        proxies.dedup();
+        proxies
+    }
+}
+
+// Where the Steam data comes from
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SteamSettings {
+    pub providers: Vec<String>, // "steamcmd", "store" or "file", the next one is asked if one fails
+    pub steamcmd_url: String,
+    pub store_url: String,
+    pub metadata_file: Option<String>, // for the file provider, laid out like SteamCMD's responses
+    pub app_list_url: String,
+    pub app_list_file: Option<String>, // use this file instead of downloading the list, e.g. for working offline
+    pub cache_ttl_secs: u64 // how long app info is cached
+}
+
+impl Default for SteamSettings {
+    fn default() -> SteamSettings {
+        SteamSettings {
+            providers: vec!["steamcmd".to_string(), "store".to_string()],
+            steamcmd_url: "https://api.steamcmd.net/v1".to_string(),
+            store_url: "https://store.steampowered.com/api".to_string(),
+            metadata_file: None,
+            app_list_url: "https://api.steampowered.com/ISteamApps/GetAppList/v2/".to_string(),
+            app_list_file: None,
+            cache_ttl_secs: 3600
+        }
+    }
+}
+
+// A download host, the fields that are left out keep their default
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct HostSettings {
+    pub kind: Option<LinkKind>, // "paste", "file", "folder" or "container"
+    pub support: Option<Support>, // "supported", "unsupported" or "ignored"
+    pub resolver: Option<Resolver>, // "direct" or "privatebin"
+    pub folders: Option<Vec<String>>, // parts of file links that make them folders
+    pub priority: Option<i32> // mirrors on hosts with a higher one are tried first
+}
+
+impl Settings {
+    // Default location of the settings file
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| home::home_dir().unwrap_or_default())
+            .join("rin_scraper")
+            .join("settings.json")
+    }
+
+    // Load the settings, the defaults are used if there's no file
+    pub fn from_file(path: &Path) -> Result<Settings, String> {
+        let f = match std::fs::File::open(path) {
+            Ok(f) => f,
+            Err(_) => return Ok(Settings::default())
+        };
+        serde_json::from_reader(f).map_err(|e| format!("Invalid settings in {}: {}", path.display(), e))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy(url: Option<&str>, hosts: &[(&str, &str)]) -> ProxySettings {
+        ProxySettings {
+            url: url.map(|u| u.to_string()),
+            hosts: hosts.iter().map(|(h, p)| (h.to_string(), p.to_string())).collect()
+        }
+    }
+
+    #[test]
+    fn picks_the_proxy_of_the_host() {
+        let settings = proxy(Some("socks5h://127.0.0.1:9050"), &[
+            ("rin.ru", "http://proxy:8080"),
+            ("cs.rin.ru", "socks5h://127.0.0.1:9150"),
+            ("steampowered.com", "DIRECT")
+        ]);
+        // The most specific host wins
+        assert_eq!(settings.for_host("cs.rin.ru").as_deref(), Some("socks5h://127.0.0.1:9150"));
+        assert_eq!(settings.for_host("forum.cs.rin.ru").as_deref(), Some("socks5h://127.0.0.1:9150"));
+        assert_eq!(settings.for_host("rin.ru").as_deref(), Some("http://proxy:8080"));
+        assert_eq!(settings.for_host("privatebin.rinuploads.org").as_deref(), Some("socks5h://127.0.0.1:9050"));
+        // Not a subdomain
+        assert_eq!(settings.for_host("notrin.ru").as_deref(), Some("socks5h://127.0.0.1:9050"));
+        // "direct" skips the default proxy too
+        assert_eq!(settings.for_host("store.steampowered.com"), None);
+
+        assert_eq!(proxy(None, &[("rin.ru", "http://proxy:8080")]).for_host("mega.nz"), None);
+        assert_eq!(ProxySettings::default().for_host("cs.rin.ru"), None);
+    }
+
+    #[test]
+    fn lists_every_proxy_once() {
+        let settings = proxy(Some("http://proxy:8080"), &[("a.example", "http://proxy:8080"), ("b.example", "direct"), ("c.example", "socks5://127.0.0.1:1080")]);
+        assert_eq!(settings.proxies(), vec!["http://proxy:8080", "socks5://127.0.0.1:1080"]);
+        assert!(proxy(None, &[("a.example", "direct")]).proxies().is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+// Info about a Steam app, from the SteamCMD API (api.steamcmd.net, which has the same data as `app_info_print`)
+// or the store's appdetails, which doesn't know about builds and branches
+
+
+// A branch of the app, "public" is the one everyone gets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Branch {
+    name: String,
+    build_id: String,
+    time_updated: String, // epoch
+    pwd_required: bool
+}
+
+impl Branch {
+    pub fn name(&self) -> String { self.name.clone() }
+    pub fn build_id(&self) -> String { self.build_id.clone() }
+    pub fn time_updated(&self) -> String { self.time_updated.clone() }
+    pub fn pwd_required(&self) -> bool { self.pwd_required }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteamInfo {
+    app_id: String,
+    title: String,
+    last_update: String, // epoch, of the public branch. Betas are updated more often, they're in branches
+    url: String,
+    build_id: String, // of the public branch
+    branches: Vec<Branch>,
+    depots: Vec<String>,
+    os_list: Vec<String>,
+    release_state: String,
+    #[serde(default)] // not in older cache entries
+    dlc: Vec<String> // app ids
+}
+
+impl SteamInfo {
+    pub fn app_id(&self) -> String { self.app_id.clone() }
+    pub fn title(&self) -> String { self.title.clone() }
+    pub fn last_update(&self) -> String { self.last_update.clone() }
+    pub fn url(&self) -> String { self.url.clone() }
+    pub fn build_id(&self) -> String { self.build_id.clone() }
+    pub fn branches(&self) -> Vec<Branch> { self.branches.clone() }
+    pub fn branch(&self, name: &str) -> Option<Branch> { self.branches.iter().find(|b| b.name == name).cloned() }
+    pub fn depots(&self) -> Vec<String> { self.depots.clone() }
+    pub fn os_list(&self) -> Vec<String> { self.os_list.clone() }
+    pub fn release_state(&self) -> String { self.release_state.clone() }
+    pub fn dlc(&self) -> Vec<String> { self.dlc.clone() }
+
+    // Parse the response of /v1/info/{appid}
+    pub fn from_steamcmd(app_id: &str, json: &Value) -> Option<SteamInfo> {
+        let app = &json["data"][app_id];
+        if !app.is_object() {
+            return None;
+        }
+        // Every value in there is a string, even the numbers
+        let string = |v: &Value| v.as_str().unwrap_or("").to_string();
+        let common = &app["common"];
+
+        let mut branches: Vec<Branch> = Vec::new();
+        if let Some(obj) = app["depots"]["branches"].as_object() {
+            for (name, branch) in obj {
+                branches.push(Branch {
+                    name: name.clone(),
+                    build_id: string(&branch["buildid"]),
+                    time_updated: branch["timeupdated"].as_str().unwrap_or("0").to_string(),
+                    pwd_required: string(&branch["pwdrequired"]) == "1"
+                });
+            }
+        }
+
+        // Depots are the entries with a numeric id, next to "branches", "baselanguages" and such
+        let depots = app["depots"].as_object()
+            .map(|obj| obj.keys().filter(|k| k.chars().all(|c| c.is_ascii_digit())).cloned().collect())
+            .unwrap_or_default();
+
+        // Apps without a public branch only have betas, the newest one is the best there is
+        let public = branches.iter().find(|b| b.name == "public");
+        let last_update = match public {
+            Some(public) => public.time_updated.clone(),
+            None => branches.iter()
+                .filter_map(|b| b.time_updated.parse::<i64>().ok())
+                .max()
+                .unwrap_or(0)
+                .to_string()
+        };
+        let build_id = public.map(|b| b.build_id.clone()).unwrap_or_default();
+
+        Some(SteamInfo {
+            app_id: app_id.to_string(),
+            title: string(&common["name"]),
+            last_update,
+            url: store_url(app_id),
+            build_id,
+            branches,
+            depots,
+            os_list: string(&common["oslist"]).split(',').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect(),
+            release_state: string(&common["releasestate"]),
+            dlc: string(&app["extended"]["listofdlc"]).split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).map(|s| s.to_string()).collect()
+        })
+    }
+
+    // Parse the response of the store's /api/appdetails?appids={appid}
+    pub fn from_store(app_id: &str, json: &Value) -> Option<SteamInfo> {
+        let app = &json[app_id];
+        if app["success"].as_bool() != Some(true) {
+            return None;
+        }
+        let data = &app["data"];
+
+        let os_list = data["platforms"].as_object()
+            .map(|p| p.iter().filter(|(_, v)| v.as_bool() == Some(true)).map(|(k, _)| k.clone()).collect())
+            .unwrap_or_default();
+        let release_state = match data["release_date"]["coming_soon"].as_bool() {
+            Some(true) => "prerelease",
+            _ => "released"
+        };
+
+        Some(SteamInfo {
+            app_id: app_id.to_string(),
+            title: data["name"].as_str()?.to_string(),
+            last_update: "0".to_string(),
+            url: store_url(app_id),
+            build_id: String::new(),
+            branches: Vec::new(),
+            depots: Vec::new(),
+            os_list,
+            release_state: release_state.to_string(),
+            dlc: data["dlc"].as_array()
+                .map(|ids| ids.iter().filter_map(|id| id.as_u64()).map(|id| id.to_string()).collect())
+                .unwrap_or_default()
+        })
+    }
+}
+
+pub fn store_url(app_id: &str) -> String {
+    format!("https://store.steampowered.com/app/{}/", app_id)
+}
+
+// App id from a store link
+pub fn app_id(url: &str) -> Option<String> {
+    let re = regex::Regex::new(r"store\.steampowered\.com/app/([0-9]+)").unwrap();
+    re.captures(url).map(|cap| cap[1].to_string())
+}
@@ -0,0 +1,130 @@
+use std::{fs, io::Error, path::PathBuf};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+// Info about Steam apps saved by app id, so checking the same games again doesn't ask Steam every time.
+// Old entries are still used when the API can't be reached
+
+
+#[derive(Serialize, Deserialize)]
+pub struct Cached {
+    pub fetched: i64, // epoch
+    pub data: Value // SteamInfo
+}
+
+pub struct SteamCache {
+    dir: Option<PathBuf>,
+    ttl: i64 // seconds
+}
+
+impl SteamCache {
+    pub fn new(dir: PathBuf, ttl: u64) -> SteamCache {
+        SteamCache { dir: Some(dir), ttl: ttl as i64 }
+    }
+
+    // Nothing is read or written, used when replaying
+    pub fn disabled() -> SteamCache {
+        SteamCache { dir: None, ttl: 0 }
+    }
+
+    pub fn default_dir() -> PathBuf {
+        dirs::cache_dir()
+            .unwrap_or_else(|| home::home_dir().unwrap_or_default())
+            .join("rin_scraper")
+            .join("steam")
+    }
+
+    fn path(&self, app_id: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{}.json", app_id)))
+    }
+
+    // The cached response, fresh or not
+    pub fn get(&self, app_id: &str) -> Option<Cached> {
+        let f = fs::File::open(self.path(app_id)?).ok()?;
+        serde_json::from_reader(f).ok()
+    }
+
+    pub fn is_fresh(&self, cached: &Cached) -> bool {
+        chrono::Utc::now().timestamp() - cached.fetched < self.ttl
+    }
+
+    pub fn put(&self, app_id: &str, data: &Value) -> Result<(), Error> {
+        let path = match self.path(app_id) {
+            Some(path) => path,
+            None => return Ok(())
+        };
+        fs::create_dir_all(path.parent().unwrap())?;
+        let f = fs::File::create(path)?;
+        serde_json::to_writer(f, &Cached { fetched: chrono::Utc::now().timestamp(), data: data.clone() })?;
+        Ok(())
+    }
+
+    // Remove every entry, returns how many there were
+    pub fn clear(&self) -> Result<usize, Error> {
+        let dir = match &self.dir {
+            Some(dir) if dir.exists() => dir,
+            _ => return Ok(0)
+        };
+        let mut removed = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().map(|e| e == "json").unwrap_or(false) {
+                fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rin_scraper_steam_cache_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn entries_expire() {
+        let dir = temp_dir("ttl");
+        let cache = SteamCache::new(dir.clone(), 3600);
+        assert!(cache.get("292030").is_none());
+
+        cache.put("292030", &json!({ "title": "The Witcher 3: Wild Hunt" })).unwrap();
+        let cached = cache.get("292030").unwrap();
+        assert_eq!(cached.data["title"], "The Witcher 3: Wild Hunt");
+        assert!(cache.is_fresh(&cached));
+        assert!(!cache.is_fresh(&Cached { fetched: cached.fetched - 3600, data: cached.data.clone() }));
+        // --no-cache
+        assert!(!SteamCache::new(dir.clone(), 0).is_fresh(&cached));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn clears_every_entry() {
+        let dir = temp_dir("clear");
+        let cache = SteamCache::new(dir.clone(), 3600);
+        assert_eq!(cache.clear().unwrap(), 0);
+
+        cache.put("292030", &json!({})).unwrap();
+        cache.put("400", &json!({})).unwrap();
+        fs::write(dir.join("notes.txt"), "not ours").unwrap();
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert!(cache.get("292030").is_none());
+        assert!(dir.join("notes.txt").exists());
+
+        // Nothing is kept without a folder
+        let disabled = SteamCache::disabled();
+        disabled.put("292030", &json!({})).unwrap();
+        assert!(disabled.get("292030").is_none());
+        assert_eq!(disabled.clear().unwrap(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+use std::path::PathBuf;
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::backend::{BackendResult, BackendError};
+use crate::display::epoch_to_date;
+use crate::request::Requester;
+use crate::session::SessionError;
+use crate::settings::SteamSettings;
+use crate::steam::SteamInfo;
+use crate::steam_cache::SteamCache;
+
+// Where info about Steam apps comes from. The providers are asked in the order from the settings
+// until one of them knows the app
+
+
+#[async_trait]
+pub trait SteamMetadataProvider: Send + Sync {
+    fn name(&self) -> String;
+    async fn app_info(&self, requests: &Requester, app_id: &str) -> BackendResult<SteamInfo>;
+}
+
+// api.steamcmd.net, has builds, branches and depots
+pub struct SteamCmdProvider {
+    base_url: String
+}
+
+#[async_trait]
+impl SteamMetadataProvider for SteamCmdProvider {
+    fn name(&self) -> String { "steamcmd".to_string() }
+
+    async fn app_info(&self, requests: &Requester, app_id: &str) -> BackendResult<SteamInfo> {
+        let json = get_json(requests, &format!("{}/info/{}", self.base_url, app_id)).await?;
+        SteamInfo::from_steamcmd(app_id, &json).ok_or_else(|| not_found(self, app_id))
+    }
+}
+
+// The store's appdetails, only has the name, platforms and release state
+pub struct StoreProvider {
+    base_url: String
+}
+
+#[async_trait]
+impl SteamMetadataProvider for StoreProvider {
+    fn name(&self) -> String { "store".to_string() }
+
+    async fn app_info(&self, requests: &Requester, app_id: &str) -> BackendResult<SteamInfo> {
+        let json = get_json(requests, &format!("{}/appdetails?appids={}", self.base_url, app_id)).await?;
+        SteamInfo::from_store(app_id, &json).ok_or_else(|| not_found(self, app_id))
+    }
+}
+
+// A local file laid out like the SteamCMD API's responses ({"data": {"appid": {...}, ...}}), for tests and offline use
+pub struct FileProvider {
+    path: PathBuf
+}
+
+#[async_trait]
+impl SteamMetadataProvider for FileProvider {
+    fn name(&self) -> String { format!("file {}", self.path.display()) }
+
+    async fn app_info(&self, _requests: &Requester, app_id: &str) -> BackendResult<SteamInfo> {
+        let json: Value = serde_json::from_slice(&std::fs::read(&self.path)?)
+            .map_err(|e| SessionError::Parse(format!("{}: {}", self.path.display(), e)))?;
+        SteamInfo::from_steamcmd(app_id, &json).ok_or_else(|| not_found(self, app_id))
+    }
+}
+
+async fn get_json(requests: &Requester, url: &str) -> BackendResult<Value> {
+    let res = requests.get(url).await?.error_for_status()?;
+    Ok(serde_json::from_str(&res.text().await?).map_err(|e| SessionError::Parse(e.to_string()))?)
+}
+
+fn not_found(provider: &dyn SteamMetadataProvider, app_id: &str) -> BackendError {
+    BackendError::NotFound(format!("app {} in {}", app_id, provider.name()))
+}
+
+// Create the providers from the settings, in their order
+pub fn from_settings(settings: &SteamSettings) -> Result<Vec<Box<dyn SteamMetadataProvider>>, String> {
+    let mut providers: Vec<Box<dyn SteamMetadataProvider>> = Vec::new();
+    for name in &settings.providers {
+        match name.as_str() {
+            "steamcmd" => providers.push(Box::new(SteamCmdProvider { base_url: settings.steamcmd_url.trim_end_matches('/').to_string() })),
+            "store" => providers.push(Box::new(StoreProvider { base_url: settings.store_url.trim_end_matches('/').to_string() })),
+            "file" => {
+                let path = settings.metadata_file.clone().ok_or("The file provider needs metadata_file in the steam settings")?;
+                providers.push(Box::new(FileProvider { path: PathBuf::from(path) }));
+            }
+            _ => return Err(format!("Unknown Steam provider: '{}', expected steamcmd, store or file", name))
+        }
+    }
+    if providers.is_empty() {
+        return Err("No Steam providers in the settings".to_string());
+    }
+    Ok(providers)
+}
+
+
+// The providers with the cache in front of them
+pub struct SteamMetadata {
+    providers: Vec<Box<dyn SteamMetadataProvider>>,
+    cache: SteamCache
+}
+
+impl SteamMetadata {
+    pub fn new(providers: Vec<Box<dyn SteamMetadataProvider>>, cache: SteamCache) -> SteamMetadata {
+        SteamMetadata { providers, cache }
+    }
+
+    // Info about the app from the cache if it's fresh, or the first provider that has it.
+    // If none of them can be reached, old info from the cache is better than nothing
+    pub async fn app_info(&self, requests: &Requester, app_id: &str) -> BackendResult<SteamInfo> {
+        let cached = self.cache.get(app_id)
+            .and_then(|c| serde_json::from_value::<SteamInfo>(c.data.clone()).ok().map(|info| (c, info)));
+        if let Some((c, info)) = &cached {
+            if self.cache.is_fresh(c) {
+                return Ok(info.clone());
+            }
+        }
+
+        let mut error = None;
+        for provider in &self.providers {
+            match provider.app_info(requests, app_id).await {
+                Ok(info) => {
+                    let stored = serde_json::to_value(&info).map_err(|e| e.into()).and_then(|v| self.cache.put(app_id, &v));
+                    if let Err(e) = stored {
+                        println!("Couldn't cache the info of app {}: {}", app_id, e);
+                    }
+                    return Ok(info);
+                }
+                Err(e) => {
+                    println!("Couldn't get app {} from {}: {}", app_id, provider.name(), e);
+                    error = Some(e);
+                }
+            }
+        }
+
+        match cached {
+            Some((c, info)) => {
+                println!("Using the info of app {} from {}", app_id, epoch_to_date(c.fetched.to_string()));
+                Ok(info)
+            }
+            None => Err(error.unwrap_or_else(|| BackendError::NotFound(format!("app {}", app_id))))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{RequestSettings, ProxySettings};
+
+    #[tokio::test]
+    async fn reads_apps_from_a_file() {
+        let settings = SteamSettings {
+            providers: vec!["file".to_string()],
+            metadata_file: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/steam_metadata.json").to_string()),
+            ..SteamSettings::default()
+        };
+        let steam = SteamMetadata::new(from_settings(&settings).unwrap(), SteamCache::disabled());
+        let requests = Requester::new(RequestSettings::default(), ProxySettings::default()).unwrap();
+
+        let info = steam.app_info(&requests, "292030").await.unwrap();
+        assert_eq!(info.title(), "The Witcher 3: Wild Hunt");
+        assert_eq!((info.build_id(), info.last_update()), ("9755563".to_string(), "1666083660".to_string()));
+        assert_eq!(info.depots(), vec!["292031", "292032"]);
+        assert_eq!(info.os_list(), vec!["windows"]);
+        assert_eq!(info.dlc(), vec!["355880", "378648", "378649"]);
+        let old = info.branch("old_patch").unwrap();
+        assert_eq!((old.build_id(), old.pwd_required()), ("5871301".to_string(), true));
+
+        assert!(matches!(steam.app_info(&requests, "400").await, Err(BackendError::NotFound(_))));
+    }
+
+    // Steam can't be reached
+    struct Offline;
+
+    #[async_trait]
+    impl SteamMetadataProvider for Offline {
+        fn name(&self) -> String { "offline".to_string() }
+        async fn app_info(&self, _requests: &Requester, app_id: &str) -> BackendResult<SteamInfo> {
+            Err(not_found(self, app_id))
+        }
+    }
+
+    fn cached_steam(name: &str, ttl: u64, fetched_ago: i64) -> (PathBuf, SteamCache) {
+        let dir = std::env::temp_dir().join(format!("rin_scraper_steam_metadata_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = SteamCache::new(dir.clone(), ttl);
+        let info = SteamInfo::from_steamcmd("292030", &serde_json::json!({ "data": { "292030": { "common": { "name": "Cached Witcher" } } } })).unwrap();
+        cache.put("292030", &serde_json::to_value(&info).unwrap()).unwrap();
+
+        // Make the entry as old as asked
+        let path = dir.join("292030.json");
+        let mut cached: crate::steam_cache::Cached = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        cached.fetched -= fetched_ago;
+        std::fs::write(&path, serde_json::to_vec(&cached).unwrap()).unwrap();
+        (dir, cache)
+    }
+
+    fn file_provider() -> Box<dyn SteamMetadataProvider> {
+        Box::new(FileProvider { path: PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/steam_metadata.json")) })
+    }
+
+    #[tokio::test]
+    async fn uses_the_cache() {
+        let requests = Requester::new(RequestSettings::default(), ProxySettings::default()).unwrap();
+
+        // Fresh entries are used without asking
+        let (dir, cache) = cached_steam("fresh", 3600, 60);
+        let steam = SteamMetadata::new(vec![Box::new(Offline)], cache);
+        assert_eq!(steam.app_info(&requests, "292030").await.unwrap().title(), "Cached Witcher");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Old ones are asked again, and replaced
+        let (dir, cache) = cached_steam("old", 3600, 7200);
+        let steam = SteamMetadata::new(vec![file_provider()], cache);
+        assert_eq!(steam.app_info(&requests, "292030").await.unwrap().title(), "The Witcher 3: Wild Hunt");
+        assert!(SteamCache::new(dir.clone(), 3600).get("292030").unwrap().data.to_string().contains("The Witcher 3: Wild Hunt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // --no-cache asks even for fresh ones
+        let (dir, cache) = cached_steam("no_cache", 0, 60);
+        let steam = SteamMetadata::new(vec![file_provider()], cache);
+        assert_eq!(steam.app_info(&requests, "292030").await.unwrap().title(), "The Witcher 3: Wild Hunt");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_old_info() {
+        let requests = Requester::new(RequestSettings::default(), ProxySettings::default()).unwrap();
+
+        let (dir, cache) = cached_steam("stale", 3600, 30 * 86400);
+        let steam = SteamMetadata::new(vec![Box::new(Offline)], cache);
+        assert_eq!(steam.app_info(&requests, "292030").await.unwrap().title(), "Cached Witcher");
+        // Nothing cached, nothing to fall back to
+        assert!(matches!(steam.app_info(&requests, "400").await, Err(BackendError::NotFound(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_provider_needs_a_file() {
+        let settings = SteamSettings { providers: vec!["file".to_string()], metadata_file: None, ..SteamSettings::default() };
+        assert!(from_settings(&settings).is_err());
+    }
+}
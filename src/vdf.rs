@@ -0,0 +1,876 @@
+use std::fmt;
+use serde::{de, ser, Serialize, Deserialize};
+use serde::de::IntoDeserializer;
+
+// Valve's KeyValues, the format of appmanifest_*.acf, libraryfolders.vdf and app info from SteamCMD.
+// The text format only has strings and nested sections, the binary one (shortcuts.vdf) also has numbers.
+// Keys can repeat, so sections keep their entries in order
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vdf {
+    Str(String),
+    Int(i32),
+    Float(f32),
+    Ptr(i32),
+    Color(i32),
+    UInt64(u64),
+    Int64(i64),
+    Map(Vec<(String, Vdf)>)
+}
+
+#[derive(Debug)]
+pub enum VdfError {
+    Parse(String),
+    Eof,
+    Custom(String)
+}
+
+impl fmt::Display for VdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VdfError::Parse(msg) => write!(f, "Couldn't parse the VDF: {}", msg),
+            VdfError::Eof => write!(f, "Couldn't parse the VDF: unexpected end of file"),
+            VdfError::Custom(msg) => write!(f, "{}", msg)
+        }
+    }
+}
+
+impl std::error::Error for VdfError {}
+
+impl de::Error for VdfError {
+    fn custom<T: fmt::Display>(msg: T) -> VdfError { VdfError::Custom(msg.to_string()) }
+}
+
+impl ser::Error for VdfError {
+    fn custom<T: fmt::Display>(msg: T) -> VdfError { VdfError::Custom(msg.to_string()) }
+}
+
+pub type VdfResult<T> = Result<T, VdfError>;
+
+impl Vdf {
+    // First value with the key, if this is a section
+    pub fn get(&self, key: &str) -> Option<&Vdf> {
+        match self {
+            Vdf::Map(entries) => entries.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, Vdf)] {
+        match self {
+            Vdf::Map(entries) => entries,
+            _ => &[]
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Vdf::Str(s) => Some(s),
+            _ => None
+        }
+    }
+
+    // Numbers are written as strings in the text format
+    fn to_text_value(&self) -> String {
+        match self {
+            Vdf::Str(s) => s.clone(),
+            Vdf::Int(n) | Vdf::Ptr(n) | Vdf::Color(n) => n.to_string(),
+            Vdf::Float(n) => n.to_string(),
+            Vdf::UInt64(n) => n.to_string(),
+            Vdf::Int64(n) => n.to_string(),
+            Vdf::Map(_) => String::new()
+        }
+    }
+
+    // The entries of a document in the text format
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        write_text(self.entries(), 0, &mut out);
+        out
+    }
+
+    // The entries of a document in the binary format
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_binary(self.entries(), &mut out);
+        out.push(END);
+        out
+    }
+}
+
+
+// Text format
+
+pub fn parse_text(text: &str) -> VdfResult<Vdf> {
+    let mut parser = TextParser { chars: text.chars().collect(), pos: 0, line: 1 };
+    let entries = parser.entries(false)?;
+    Ok(Vdf::Map(entries))
+}
+
+struct TextParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize
+}
+
+enum Token {
+    Str(String),
+    Open,
+    Close
+}
+
+impl TextParser {
+    fn error(&self, msg: &str) -> VdfError {
+        VdfError::Parse(format!("{} on line {}", msg, self.line))
+    }
+
+    fn peek(&self) -> Option<char> { self.chars.get(self.pos).copied() }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    // Skip whitespace and // comments
+    fn skip(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '/' && self.chars.get(self.pos + 1) == Some(&'/') {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.bump();
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> VdfResult<Option<Token>> {
+        self.skip();
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok(None)
+        };
+        match c {
+            '{' => { self.bump(); Ok(Some(Token::Open)) }
+            '}' => { self.bump(); Ok(Some(Token::Close)) }
+            '"' => {
+                self.bump();
+                let mut s = String::new();
+                loop {
+                    match self.bump().ok_or(VdfError::Eof)? {
+                        '"' => break,
+                        '\\' => match self.bump().ok_or(VdfError::Eof)? {
+                            'n' => s.push('\n'),
+                            't' => s.push('\t'),
+                            'r' => s.push('\r'),
+                            c @ ('\\' | '"') => s.push(c),
+                            // Not an escape, e.g. a single backslash in a path
+                            c => { s.push('\\'); s.push(c) }
+                        },
+                        c => s.push(c)
+                    }
+                }
+                Ok(Some(Token::Str(s)))
+            }
+            _ => {
+                let mut s = String::new();
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                        break;
+                    }
+                    s.push(c);
+                    self.bump();
+                }
+                Ok(Some(Token::Str(s)))
+            }
+        }
+    }
+
+    // Conditions like [$WIN32] after a value are ignored
+    fn skip_condition(&mut self) {
+        self.skip();
+        if self.peek() == Some('[') {
+            while !matches!(self.bump(), None | Some(']')) {}
+        }
+    }
+
+    // Entries until the closing brace of the section, or the end of the file at the top
+    fn entries(&mut self, nested: bool) -> VdfResult<Vec<(String, Vdf)>> {
+        let mut entries = Vec::new();
+        loop {
+            let key = match self.token()? {
+                Some(Token::Str(key)) => key,
+                Some(Token::Close) if nested => return Ok(entries),
+                None if !nested => return Ok(entries),
+                Some(Token::Close) => return Err(self.error("Unexpected '}'")),
+                Some(Token::Open) => return Err(self.error("Expected a key, found '{'")),
+                None => return Err(VdfError::Eof)
+            };
+            self.skip_condition();
+            let value = match self.token()? {
+                Some(Token::Str(value)) => Vdf::Str(value),
+                Some(Token::Open) => Vdf::Map(self.entries(true)?),
+                Some(Token::Close) => return Err(self.error(&format!("Missing value for '{}'", key))),
+                None => return Err(VdfError::Eof)
+            };
+            self.skip_condition();
+            entries.push((key, value));
+        }
+    }
+}
+
+fn quote(s: &str) -> String {
+    let escaped = s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+fn write_text(entries: &[(String, Vdf)], depth: usize, out: &mut String) {
+    let indent = "\t".repeat(depth);
+    for (key, value) in entries {
+        match value {
+            Vdf::Map(children) => {
+                out.push_str(&format!("{indent}{}\n{indent}{{\n", quote(key)));
+                write_text(children, depth + 1, out);
+                out.push_str(&format!("{indent}}}\n"));
+            }
+            _ => out.push_str(&format!("{indent}{}\t\t{}\n", quote(key), quote(&value.to_text_value())))
+        }
+    }
+}
+
+
+// Binary format: a type byte, the key as a C string and the value, sections end with END
+
+const MAP: u8 = 0x00;
+const STR: u8 = 0x01;
+const INT: u8 = 0x02;
+const FLOAT: u8 = 0x03;
+const PTR: u8 = 0x04;
+const WIDE_STR: u8 = 0x05;
+const COLOR: u8 = 0x06;
+const UINT64: u8 = 0x07;
+const END: u8 = 0x08;
+const INT64: u8 = 0x0A;
+
+pub fn parse_binary(bytes: &[u8]) -> VdfResult<Vdf> {
+    let mut parser = BinaryParser { bytes, pos: 0 };
+    Ok(Vdf::Map(parser.entries()?))
+}
+
+struct BinaryParser<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> BinaryParser<'a> {
+    fn take(&mut self, n: usize) -> VdfResult<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos + n).ok_or(VdfError::Eof)?;
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn cstring(&mut self) -> VdfResult<String> {
+        let len = self.bytes[self.pos.min(self.bytes.len())..].iter().position(|b| *b == 0).ok_or(VdfError::Eof)?;
+        let s = String::from_utf8_lossy(self.take(len)?).to_string();
+        self.pos += 1;
+        Ok(s)
+    }
+
+    // UTF-16, ends with two zero bytes
+    fn wide_string(&mut self) -> VdfResult<String> {
+        let mut units = Vec::new();
+        loop {
+            let unit = u16::from_le_bytes(self.take(2)?.try_into().unwrap());
+            if unit == 0 {
+                return Ok(String::from_utf16_lossy(&units));
+            }
+            units.push(unit);
+        }
+    }
+
+    fn i32(&mut self) -> VdfResult<i32> { Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+
+    // Entries until END, the top level may also just stop
+    fn entries(&mut self) -> VdfResult<Vec<(String, Vdf)>> {
+        let mut entries = Vec::new();
+        loop {
+            let kind = match self.take(1) {
+                Ok(b) => b[0],
+                Err(_) => return Ok(entries)
+            };
+            if kind == END {
+                return Ok(entries);
+            }
+            let key = self.cstring()?;
+            let value = match kind {
+                MAP => Vdf::Map(self.entries()?),
+                STR => Vdf::Str(self.cstring()?),
+                INT => Vdf::Int(self.i32()?),
+                FLOAT => Vdf::Float(f32::from_le_bytes(self.take(4)?.try_into().unwrap())),
+                PTR => Vdf::Ptr(self.i32()?),
+                WIDE_STR => Vdf::Str(self.wide_string()?),
+                COLOR => Vdf::Color(self.i32()?),
+                UINT64 => Vdf::UInt64(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+                INT64 => Vdf::Int64(i64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+                _ => return Err(VdfError::Parse(format!("Unknown type {:#04x} at byte {}", kind, self.pos - key.len() - 2)))
+            };
+            entries.push((key, value));
+        }
+    }
+}
+
+fn write_binary(entries: &[(String, Vdf)], out: &mut Vec<u8>) {
+    let cstring = |s: &str, out: &mut Vec<u8>| {
+        out.extend_from_slice(s.as_bytes());
+        out.push(0);
+    };
+    for (key, value) in entries {
+        let kind = match value {
+            Vdf::Map(_) => MAP,
+            Vdf::Str(_) => STR,
+            Vdf::Int(_) => INT,
+            Vdf::Float(_) => FLOAT,
+            Vdf::Ptr(_) => PTR,
+            Vdf::Color(_) => COLOR,
+            Vdf::UInt64(_) => UINT64,
+            Vdf::Int64(_) => INT64
+        };
+        out.push(kind);
+        cstring(key, out);
+        match value {
+            Vdf::Map(children) => {
+                write_binary(children, out);
+                out.push(END);
+            }
+            Vdf::Str(s) => cstring(s, out),
+            Vdf::Int(n) | Vdf::Ptr(n) | Vdf::Color(n) => out.extend_from_slice(&n.to_le_bytes()),
+            Vdf::Float(n) => out.extend_from_slice(&n.to_le_bytes()),
+            Vdf::UInt64(n) => out.extend_from_slice(&n.to_le_bytes()),
+            Vdf::Int64(n) => out.extend_from_slice(&n.to_le_bytes())
+        }
+    }
+}
+
+
+// Serde. Documents have a single root section, its value is what gets (de)serialized
+
+fn root(doc: Vdf) -> VdfResult<Vdf> {
+    match doc {
+        Vdf::Map(mut entries) if !entries.is_empty() => Ok(entries.swap_remove(0).1),
+        _ => Err(VdfError::Parse("Empty document".to_string()))
+    }
+}
+
+pub fn from_vdf<'de, T: Deserialize<'de>>(vdf: &'de Vdf) -> VdfResult<T> {
+    T::deserialize(vdf)
+}
+
+pub fn from_str<T: de::DeserializeOwned>(text: &str) -> VdfResult<T> {
+    from_vdf(&root(parse_text(text)?)?)
+}
+
+pub fn from_bytes<T: de::DeserializeOwned>(bytes: &[u8]) -> VdfResult<T> {
+    from_vdf(&root(parse_binary(bytes)?)?)
+}
+
+pub fn to_vdf<T: Serialize>(value: &T) -> VdfResult<Vdf> {
+    value.serialize(Serializer)?.ok_or_else(|| VdfError::Custom("Nothing to serialize".to_string()))
+}
+
+pub fn to_string<T: Serialize>(key: &str, value: &T) -> VdfResult<String> {
+    Ok(Vdf::Map(vec![(key.to_string(), to_vdf(value)?)]).to_text())
+}
+
+pub fn to_bytes<T: Serialize>(key: &str, value: &T) -> VdfResult<Vec<u8>> {
+    Ok(Vdf::Map(vec![(key.to_string(), to_vdf(value)?)]).to_binary())
+}
+
+
+// Strings are parsed when a number or bool is expected, the text format has nothing else
+macro_rules! deserialize_number {
+    ($method:ident, $visit:ident, $ty:ty) => {
+        fn $method<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+            let n: $ty = match self {
+                Vdf::Str(s) => s.trim().parse().map_err(|_| VdfError::Custom(format!("Expected a number, found '{}'", s)))?,
+                Vdf::Int(n) | Vdf::Ptr(n) | Vdf::Color(n) => *n as $ty,
+                Vdf::Float(n) => *n as $ty,
+                Vdf::UInt64(n) => *n as $ty,
+                Vdf::Int64(n) => *n as $ty,
+                Vdf::Map(_) => return Err(VdfError::Custom("Expected a number, found a section".to_string()))
+            };
+            visitor.$visit(n)
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &'de Vdf {
+    type Error = VdfError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+        match self {
+            Vdf::Str(s) => visitor.visit_borrowed_str(s),
+            Vdf::Int(n) | Vdf::Ptr(n) | Vdf::Color(n) => visitor.visit_i32(*n),
+            Vdf::Float(n) => visitor.visit_f32(*n),
+            Vdf::UInt64(n) => visitor.visit_u64(*n),
+            Vdf::Int64(n) => visitor.visit_i64(*n),
+            Vdf::Map(entries) => visitor.visit_map(MapAccess { entries: entries.iter(), value: None })
+        }
+    }
+
+    fn deserialize_bool<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+        match self {
+            Vdf::Str(s) => match s.trim() {
+                "1" | "true" => visitor.visit_bool(true),
+                "0" | "false" | "" => visitor.visit_bool(false),
+                _ => Err(VdfError::Custom(format!("Expected a bool, found '{}'", s)))
+            },
+            Vdf::Int(n) => visitor.visit_bool(*n != 0),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    deserialize_number!(deserialize_i8, visit_i8, i8);
+    deserialize_number!(deserialize_i16, visit_i16, i16);
+    deserialize_number!(deserialize_i32, visit_i32, i32);
+    deserialize_number!(deserialize_i64, visit_i64, i64);
+    deserialize_number!(deserialize_u8, visit_u8, u8);
+    deserialize_number!(deserialize_u16, visit_u16, u16);
+    deserialize_number!(deserialize_u32, visit_u32, u32);
+    deserialize_number!(deserialize_u64, visit_u64, u64);
+    deserialize_number!(deserialize_f32, visit_f32, f32);
+    deserialize_number!(deserialize_f64, visit_f64, f64);
+
+    fn deserialize_string<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+        match self {
+            Vdf::Map(_) => self.deserialize_any(visitor),
+            _ => visitor.visit_string(self.to_text_value())
+        }
+    }
+
+    fn deserialize_str<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> VdfResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Lists are sections with the keys "0", "1", ...
+    fn deserialize_seq<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+        match self {
+            Vdf::Map(entries) => visitor.visit_seq(SeqAccess { values: entries.iter().map(|(_, v)| v) }),
+            _ => Err(VdfError::Custom("Expected a section for a list".to_string()))
+        }
+    }
+
+    fn deserialize_tuple<V: de::Visitor<'de>>(self, _len: usize, visitor: V) -> VdfResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> VdfResult<V::Value> {
+        match self {
+            Vdf::Str(s) => visitor.visit_enum(s.as_str().into_deserializer()),
+            _ => Err(VdfError::Custom("Only enums without data are supported".to_string()))
+        }
+    }
+
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> VdfResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        char bytes byte_buf unit_struct tuple_struct map struct identifier
+    }
+}
+
+struct MapAccess<'de, I: Iterator<Item = &'de (String, Vdf)>> {
+    entries: I,
+    value: Option<&'de Vdf>
+}
+
+impl<'de, I: Iterator<Item = &'de (String, Vdf)>> de::MapAccess<'de> for MapAccess<'de, I> {
+    type Error = VdfError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> VdfResult<Option<K::Value>> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.as_str().into_deserializer()).map(Some)
+            }
+            None => Ok(None)
+        }
+    }
+
+    fn next_value_seed<S: de::DeserializeSeed<'de>>(&mut self, seed: S) -> VdfResult<S::Value> {
+        seed.deserialize(self.value.take().ok_or(VdfError::Eof)?)
+    }
+}
+
+struct SeqAccess<'de, I: Iterator<Item = &'de Vdf>> {
+    values: I
+}
+
+impl<'de, I: Iterator<Item = &'de Vdf>> de::SeqAccess<'de> for SeqAccess<'de, I> {
+    type Error = VdfError;
+
+    fn next_element_seed<S: de::DeserializeSeed<'de>>(&mut self, seed: S) -> VdfResult<Option<S::Value>> {
+        self.values.next().map(|v| seed.deserialize(v)).transpose()
+    }
+}
+
+
+// Serializes into a Vdf, None when there's nothing to write (None and unit), so those fields are left out
+struct Serializer;
+
+struct SectionSerializer {
+    entries: Vec<(String, Vdf)>,
+    key: Option<String>,
+    variant: Option<&'static str>
+}
+
+impl SectionSerializer {
+    fn new(variant: Option<&'static str>) -> SectionSerializer {
+        SectionSerializer { entries: Vec::new(), key: None, variant }
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> VdfResult<()> {
+        if let Some(value) = value.serialize(Serializer)? {
+            self.entries.push((key, value));
+        }
+        Ok(())
+    }
+
+    fn end(self) -> VdfResult<Option<Vdf>> {
+        let section = Vdf::Map(self.entries);
+        Ok(Some(match self.variant {
+            Some(variant) => Vdf::Map(vec![(variant.to_string(), section)]),
+            None => section
+        }))
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Vdf>;
+    type Error = VdfError;
+    type SerializeSeq = SectionSerializer;
+    type SerializeTuple = SectionSerializer;
+    type SerializeTupleStruct = SectionSerializer;
+    type SerializeTupleVariant = SectionSerializer;
+    type SerializeMap = SectionSerializer;
+    type SerializeStruct = SectionSerializer;
+    type SerializeStructVariant = SectionSerializer;
+
+    fn serialize_bool(self, v: bool) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Int(v as i32))) }
+    fn serialize_i8(self, v: i8) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Int(v as i32))) }
+    fn serialize_i16(self, v: i16) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Int(v as i32))) }
+    fn serialize_i32(self, v: i32) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Int(v))) }
+    fn serialize_i64(self, v: i64) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Int64(v))) }
+    fn serialize_u8(self, v: u8) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Int(v as i32))) }
+    fn serialize_u16(self, v: u16) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Int(v as i32))) }
+    fn serialize_u32(self, v: u32) -> VdfResult<Option<Vdf>> {
+        Ok(Some(match i32::try_from(v) {
+            Ok(n) => Vdf::Int(n),
+            Err(_) => Vdf::UInt64(v as u64)
+        }))
+    }
+    fn serialize_u64(self, v: u64) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::UInt64(v))) }
+    fn serialize_f32(self, v: f32) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Float(v))) }
+    fn serialize_f64(self, v: f64) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Float(v as f32))) }
+    fn serialize_char(self, v: char) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Str(v.to_string()))) }
+    fn serialize_str(self, v: &str) -> VdfResult<Option<Vdf>> { Ok(Some(Vdf::Str(v.to_string()))) }
+
+    fn serialize_bytes(self, _v: &[u8]) -> VdfResult<Option<Vdf>> {
+        Err(VdfError::Custom("Bytes can't be written to VDF".to_string()))
+    }
+
+    fn serialize_none(self) -> VdfResult<Option<Vdf>> { Ok(None) }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> VdfResult<Option<Vdf>> { value.serialize(self) }
+    fn serialize_unit(self) -> VdfResult<Option<Vdf>> { Ok(None) }
+    fn serialize_unit_struct(self, _name: &'static str) -> VdfResult<Option<Vdf>> { Ok(None) }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> VdfResult<Option<Vdf>> {
+        Ok(Some(Vdf::Str(variant.to_string())))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> VdfResult<Option<Vdf>> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> VdfResult<Option<Vdf>> {
+        let mut section = SectionSerializer::new(None);
+        section.push(variant.to_string(), value)?;
+        section.end()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> VdfResult<SectionSerializer> { Ok(SectionSerializer::new(None)) }
+    fn serialize_tuple(self, _len: usize) -> VdfResult<SectionSerializer> { Ok(SectionSerializer::new(None)) }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> VdfResult<SectionSerializer> {
+        Ok(SectionSerializer::new(None))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> VdfResult<SectionSerializer> {
+        Ok(SectionSerializer::new(Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> VdfResult<SectionSerializer> { Ok(SectionSerializer::new(None)) }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> VdfResult<SectionSerializer> {
+        Ok(SectionSerializer::new(None))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> VdfResult<SectionSerializer> {
+        Ok(SectionSerializer::new(Some(variant)))
+    }
+}
+
+// Lists get their index as the key
+impl ser::SerializeSeq for SectionSerializer {
+    type Ok = Option<Vdf>;
+    type Error = VdfError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> VdfResult<()> {
+        let key = self.entries.len().to_string();
+        self.push(key, value)
+    }
+
+    fn end(self) -> VdfResult<Option<Vdf>> { SectionSerializer::end(self) }
+}
+
+impl ser::SerializeTuple for SectionSerializer {
+    type Ok = Option<Vdf>;
+    type Error = VdfError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> VdfResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> VdfResult<Option<Vdf>> { SectionSerializer::end(self) }
+}
+
+impl ser::SerializeTupleStruct for SectionSerializer {
+    type Ok = Option<Vdf>;
+    type Error = VdfError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> VdfResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> VdfResult<Option<Vdf>> { SectionSerializer::end(self) }
+}
+
+impl ser::SerializeTupleVariant for SectionSerializer {
+    type Ok = Option<Vdf>;
+    type Error = VdfError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> VdfResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> VdfResult<Option<Vdf>> { SectionSerializer::end(self) }
+}
+
+impl ser::SerializeMap for SectionSerializer {
+    type Ok = Option<Vdf>;
+    type Error = VdfError;
+
+    // Keys are always strings, numbers are written as text
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> VdfResult<()> {
+        let key = match key.serialize(Serializer)? {
+            Some(Vdf::Map(_)) | None => return Err(VdfError::Custom("Keys have to be strings or numbers".to_string())),
+            Some(key) => key.to_text_value()
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> VdfResult<()> {
+        let key = self.key.take().ok_or_else(|| VdfError::Custom("Value without a key".to_string()))?;
+        self.push(key, value)
+    }
+
+    fn end(self) -> VdfResult<Option<Vdf>> { SectionSerializer::end(self) }
+}
+
+impl ser::SerializeStruct for SectionSerializer {
+    type Ok = Option<Vdf>;
+    type Error = VdfError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> VdfResult<()> {
+        self.push(key.to_string(), value)
+    }
+
+    fn end(self) -> VdfResult<Option<Vdf>> { SectionSerializer::end(self) }
+}
+
+impl ser::SerializeStructVariant for SectionSerializer {
+    type Ok = Option<Vdf>;
+    type Error = VdfError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> VdfResult<()> {
+        self.push(key.to_string(), value)
+    }
+
+    fn end(self) -> VdfResult<Option<Vdf>> { SectionSerializer::end(self) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const MANIFEST: &str = r#"
+"AppState"
+{
+	"appid"		"292030"
+	"name"		"The Witcher 3: Wild Hunt"
+	"buildid"		"8364418" // comment
+	"installdir"		"The Witcher 3"
+	"UserConfig"
+	{
+		"BetaKey"		"public"
+	}
+	"InstalledDepots"
+	{
+		"292031"
+		{
+			"manifest"		"7265812346543123"
+			"size"		"12345"
+		}
+	}
+}
+"#;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct AppState {
+        appid: u32,
+        name: String,
+        buildid: u64,
+        installdir: String,
+        #[serde(rename = "UserConfig")]
+        user_config: HashMap<String, String>,
+        #[serde(rename = "InstalledDepots")]
+        installed_depots: HashMap<String, Depot>,
+        #[serde(rename = "LastOwner")]
+        last_owner: Option<String>
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Depot {
+        manifest: String,
+        size: u64
+    }
+
+    #[test]
+    fn parses_text() {
+        let doc = parse_text(MANIFEST).unwrap();
+        let app = doc.get("AppState").unwrap();
+        assert_eq!(app.get("buildid").and_then(Vdf::as_str), Some("8364418"));
+        assert_eq!(app.get("UserConfig").and_then(|c| c.get("betakey")).and_then(Vdf::as_str), Some("public"));
+        assert_eq!(app.get("InstalledDepots").unwrap().entries()[0].0, "292031");
+    }
+
+    #[test]
+    fn deserializes_text() {
+        let app: AppState = from_str(MANIFEST).unwrap();
+        assert_eq!(app.appid, 292030);
+        assert_eq!(app.buildid, 8364418);
+        assert_eq!(app.installed_depots["292031"], Depot { manifest: "7265812346543123".to_string(), size: 12345 });
+        assert_eq!(app.last_owner, None);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let doc = parse_text(MANIFEST).unwrap();
+        assert_eq!(parse_text(&doc.to_text()).unwrap(), doc);
+
+        let app: AppState = from_str(MANIFEST).unwrap();
+        let again: AppState = from_str(&to_string("AppState", &app).unwrap()).unwrap();
+        assert_eq!(again, app);
+    }
+
+    #[test]
+    fn escapes_and_unquoted() {
+        let doc = parse_text("libraryfolders { 0 { path \"C:\\\\Program Files (x86)\\\\Steam\" label \"say \\\"hi\\\"\" } }").unwrap();
+        let folder = doc.get("libraryfolders").and_then(|f| f.get("0")).unwrap();
+        assert_eq!(folder.get("path").and_then(Vdf::as_str), Some("C:\\Program Files (x86)\\Steam"));
+        assert_eq!(folder.get("label").and_then(Vdf::as_str), Some("say \"hi\""));
+        assert_eq!(parse_text(&doc.to_text()).unwrap(), doc);
+    }
+
+    #[test]
+    fn conditions_are_ignored() {
+        let doc = parse_text("\"a\" { \"b\" \"1\" [$WIN32] \"c\" \"2\" }").unwrap();
+        assert_eq!(doc.get("a").unwrap().entries().len(), 2);
+    }
+
+    #[test]
+    fn text_errors() {
+        assert!(matches!(parse_text("\"a\" { \"b\" \"1\""), Err(VdfError::Eof)));
+        assert!(matches!(parse_text("\"a\" }"), Err(VdfError::Parse(_))));
+        assert!(matches!(parse_text("\"a\" \"unterminated"), Err(VdfError::Eof)));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Shortcut {
+        appid: i32,
+        #[serde(rename = "AppName")]
+        app_name: String,
+        #[serde(rename = "Exe")]
+        exe: String,
+        #[serde(rename = "IsHidden")]
+        is_hidden: bool,
+        #[serde(rename = "LastPlayTime")]
+        last_play_time: i32,
+        tags: Vec<String>
+    }
+
+    fn shortcuts() -> Vec<Shortcut> {
+        vec![Shortcut {
+            appid: -123456789,
+            app_name: "Game".to_string(),
+            exe: "\"C:\\Games\\Game\\game.exe\"".to_string(),
+            is_hidden: false,
+            last_play_time: 1665000000,
+            tags: vec!["favorite".to_string(), "SCS".to_string()]
+        }]
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let bytes = to_bytes("shortcuts", &shortcuts()).unwrap();
+        let doc = parse_binary(&bytes).unwrap();
+        assert_eq!(doc.to_binary(), bytes);
+
+        let shortcut = doc.get("shortcuts").and_then(|s| s.get("0")).unwrap();
+        assert_eq!(shortcut.get("appid"), Some(&Vdf::Int(-123456789)));
+        assert_eq!(shortcut.get("tags").and_then(|t| t.get("1")).and_then(Vdf::as_str), Some("SCS"));
+
+        let again: Vec<Shortcut> = from_bytes(&bytes).unwrap();
+        assert_eq!(again, shortcuts());
+    }
+
+    #[test]
+    fn binary_layout() {
+        let bytes = to_bytes("s", &HashMap::from([("k", 1)])).unwrap();
+        assert_eq!(bytes, b"\x00s\x00\x02k\x00\x01\x00\x00\x00\x08\x08");
+        assert!(matches!(parse_binary(b"\x00s\x00\x02k\x00\x01"), Err(VdfError::Eof)));
+        assert!(matches!(parse_binary(b"\x09k\x00"), Err(VdfError::Parse(_))));
+    }
+}